@@ -0,0 +1,54 @@
+#!/bin/sh
+# Checks the claims of the commit replacing the regex scanner with the
+# hand-written lexer: both produce identical tokens for every scanner test
+# program and the lexer is at least 10 times faster on the benchmark corpus.
+#
+# Run from the repository root: sh benches/compare_regex_scanner.sh
+set -eu
+
+lexer=$(git log --format=%H --grep='^\[user-001\] Replace regex-per-token scanner' | tail -n 1)
+if [ -z "$lexer" ]; then
+    echo "Cannot find the commit replacing the regex scanner" >&2
+    exit 1
+fi
+work=$(mktemp -d)
+trap 'git worktree remove --force "$work/regex" 2>/dev/null;
+      git worktree remove --force "$work/lexer" 2>/dev/null;
+      rm -rf "$work"' EXIT
+git worktree add -q --detach "$work/regex" "$lexer^"
+git worktree add -q --detach "$work/lexer" "$lexer"
+
+# the regex scanner predates the benchmark
+mkdir -p "$work/regex/benches"
+cp "$work/lexer/benches/scanning.rs" "$work/regex/benches/scanning.rs"
+printf '\n[[bench]]\nname = "scanning"\nharness = false\n' >> "$work/regex/Cargo.toml"
+
+for tree in regex lexer; do
+    (cd "$work/$tree" && CARGO_TARGET_DIR="$work/target-$tree" cargo build -q --release)
+done
+
+status=0
+for program in "$work"/lexer/tests/scanner_programs/*/*.bminor; do
+    "$work/target-regex/release/rbm" lex "$program" > "$work/regex.out"
+    "$work/target-lexer/release/rbm" lex "$program" > "$work/lexer.out"
+    if ! cmp -s "$work/regex.out" "$work/lexer.out"; then
+        echo "Tokens differ for ${program#"$work"/lexer/}"
+        status=1
+    fi
+done
+
+# prints the time per iteration reported by the benchmark in seconds
+seconds() {
+    (cd "$work/$1" && CARGO_TARGET_DIR="$work/target-$1" cargo bench -q --bench scanning) |
+        sed -n 's/.* tokens in \([0-9.]*\)\([a-zµ]*\) .*/\1 \2/p' |
+        awk '{ scale = ($2 == "s") ? 1 : ($2 == "ms") ? 1e-3 : ($2 == "µs") ? 1e-6 : 1e-9;
+               print $1 * scale }'
+}
+regex=$(seconds regex)
+lexer=$(seconds lexer)
+echo "regex scanner: ${regex}s, lexer: ${lexer}s per iteration"
+if ! awk -v regex="$regex" -v lexer="$lexer" 'BEGIN { exit !(regex >= 10 * lexer) }'; then
+    echo "The lexer is less than 10 times faster"
+    status=1
+fi
+exit $status
@@ -0,0 +1,48 @@
+//! Measures the lexer throughput.
+//!
+//! The regex-per-token scanner that the lexer replaced needed 26.7s per
+//! iteration on the corpus of that time (25500 lines, 78200 tokens), the
+//! lexer needed 7.8ms in the same run. `compare_regex_scanner.sh` next to
+//! this file reproduces the comparison.
+
+use rbm::scanner;
+use std::fs;
+use std::time::Instant;
+
+const REPETITIONS: usize = 100;
+const ITERATIONS: u32 = 20;
+
+fn make_corpus() -> String {
+    let mut corpus = String::new();
+    let mut paths: Vec<_> = fs::read_dir("./tests/scanner_programs/correct")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    for path in paths {
+        let content = fs::read_to_string(path).unwrap();
+        // a byte order mark is only allowed at the beginning of the corpus
+        corpus.push_str(content.trim_start_matches('\u{feff}'));
+        corpus.push('\n');
+    }
+    corpus.repeat(REPETITIONS)
+}
+
+fn main() {
+    let corpus = make_corpus();
+    let lines = corpus.lines().count();
+    let start = Instant::now();
+    let mut tokens = 0;
+    for _ in 0..ITERATIONS {
+        tokens = scanner::tokenize(&corpus).unwrap().len();
+    }
+    let per_iteration = start.elapsed() / ITERATIONS;
+    println!(
+        "tokenize: {} lines, {} bytes, {} tokens in {:?} ({:.2} MB/s)",
+        lines,
+        corpus.len(),
+        tokens,
+        per_iteration,
+        corpus.len() as f64 / per_iteration.as_secs_f64() / 1_000_000.0
+    );
+}
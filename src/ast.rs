@@ -1,12 +1,18 @@
+use crate::source::Span;
+use std::fmt;
+
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum StatementKind {
     Declaration,
     Expression,
     IfElse,
     For,
+    While,
     Print,
     Return,
     Block,
+    /// Statement that could not be parsed.
+    Error,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -36,7 +42,74 @@ pub enum ExpressionKind {
     Call,
     Subscript,
     FunctionCall,
-    FunctionArg,
+}
+
+impl ExpressionKind {
+    /// Returns the type of an arithmetic or comparison expression of this
+    /// kind whose operands are both integers or both floats. Mixed and
+    /// non-numeric operands give `None`, their rules are left to the future
+    /// type checker these are meant for.
+    pub fn binary_result_type(&self, left: &TypeKind, right: &TypeKind) -> Option<TypeKind> {
+        let numeric = *left == TypeKind::Integer || *left == TypeKind::Float;
+        if left != right || !numeric {
+            return None;
+        }
+        match self {
+            ExpressionKind::Addition
+            | ExpressionKind::Subtraction
+            | ExpressionKind::Multiplication
+            | ExpressionKind::Division
+            | ExpressionKind::Power => Some(left.clone()),
+            ExpressionKind::Modulo if *left == TypeKind::Integer => Some(TypeKind::Integer),
+            ExpressionKind::Less
+            | ExpressionKind::LessEqual
+            | ExpressionKind::More
+            | ExpressionKind::MoreEqual
+            | ExpressionKind::Equal
+            | ExpressionKind::NotEqual => Some(TypeKind::Boolean),
+            _ => None,
+        }
+    }
+
+    /// Returns the type of an arithmetic unary expression of this kind whose
+    /// operand has the given type or `None` if the operand is not allowed.
+    pub fn unary_result_type(&self, operand: &TypeKind) -> Option<TypeKind> {
+        let numeric = *operand == TypeKind::Integer || *operand == TypeKind::Float;
+        match self {
+            ExpressionKind::Minus
+            | ExpressionKind::Incrementation
+            | ExpressionKind::Decrementation
+                if numeric =>
+            {
+                Some(operand.clone())
+            }
+            _ => None,
+        }
+    }
+
+    fn operator(&self) -> &'static str {
+        match self {
+            ExpressionKind::Assignment => "=",
+            ExpressionKind::And => "&&",
+            ExpressionKind::Or => "||",
+            ExpressionKind::Less => "<",
+            ExpressionKind::LessEqual => "<=",
+            ExpressionKind::More => ">",
+            ExpressionKind::MoreEqual => ">=",
+            ExpressionKind::Equal => "==",
+            ExpressionKind::NotEqual => "!=",
+            ExpressionKind::Addition => "+",
+            ExpressionKind::Subtraction | ExpressionKind::Minus => "-",
+            ExpressionKind::Multiplication => "*",
+            ExpressionKind::Division => "/",
+            ExpressionKind::Modulo => "%",
+            ExpressionKind::Power => "^",
+            ExpressionKind::Negation => "!",
+            ExpressionKind::Incrementation => "++",
+            ExpressionKind::Decrementation => "--",
+            _ => "",
+        }
+    }
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -45,28 +118,34 @@ pub enum TypeKind {
     Boolean,
     Character,
     Integer,
+    Float,
     Text,
     Array,
     Function,
+    /// Type of a declaration that could not be parsed.
+    Error,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Type {
     pub kind: TypeKind,
     pub subtype: Option<Box<Type>>,
-    pub param_list: Option<ParameterList>,
+    /// Declared size of an array, `None` for arrays of unspecified size.
+    pub size: Option<usize>,
+    pub param_list: Option<Box<ParameterList>>,
 }
 
 impl Type {
     pub fn from_name(name: String) -> Option<Type> {
         let type_ = match name.as_str() {
-            "void"=> Type {kind: TypeKind::Void, subtype: None, param_list: None},
-            "boolean"=> Type {kind: TypeKind::Boolean, subtype: None, param_list: None},
-            "string"=> Type {kind: TypeKind::Text, subtype: None, param_list: None},
-            "function"=> Type {kind: TypeKind::Function, subtype: None, param_list: None},
-            "array"=> Type {kind: TypeKind::Array, subtype: None, param_list: None},
-            "integer"=> Type {kind: TypeKind::Integer, subtype: None, param_list: None},
-            "char"=> Type {kind: TypeKind::Character, subtype: None, param_list: None},
+            "void"=> Type {kind: TypeKind::Void, subtype: None, size: None, param_list: None},
+            "boolean"=> Type {kind: TypeKind::Boolean, subtype: None, size: None, param_list: None},
+            "string"=> Type {kind: TypeKind::Text, subtype: None, size: None, param_list: None},
+            "function"=> Type {kind: TypeKind::Function, subtype: None, size: None, param_list: None},
+            "array"=> Type {kind: TypeKind::Array, subtype: None, size: None, param_list: None},
+            "integer"=> Type {kind: TypeKind::Integer, subtype: None, size: None, param_list: None},
+            "float"=> Type {kind: TypeKind::Float, subtype: None, size: None, param_list: None},
+            "char"=> Type {kind: TypeKind::Character, subtype: None, size: None, param_list: None},
             _ => return None,
         };
         Some(type_)
@@ -82,14 +161,37 @@ impl Type {
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct ParameterList {
-    pub name: String,
-    pub kind: TypeKind,
+    /// Name of the parameter, `None` for parameters of function types that
+    /// are themselves parameter or return types.
+    pub name: Option<String>,
+    pub type_: Type,
+    pub span: Span,
     pub next: Option<Box<ParameterList>>,
 }
 
+impl ParameterList {
+    pub fn new(name: Option<String>, type_: Type, span: Span) -> Self {
+        ParameterList {
+            name,
+            type_,
+            span,
+            next: None,
+        }
+    }
+
+    pub fn attach_most_next(&mut self, parameter: ParameterList) {
+        match &mut self.next {
+            None => self.next = Some(Box::new(parameter)),
+            Some(nexter) => nexter.attach_most_next(parameter),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Declaration {
     pub name: String,
+    /// Text of the doc comments written before the declaration.
+    pub doc: Option<String>,
     pub type_: Type,
     pub value: Option<Expression>,
     pub code: Option<Box<Statement>>,
@@ -100,6 +202,7 @@ impl Declaration {
     pub fn new_value(name: String, type_: Type, value: Option<Expression>) -> Self {
         Declaration {
             name,
+            doc: None,
             type_,
             value,
             code: None,
@@ -110,19 +213,34 @@ impl Declaration {
     pub fn new_function(name: String, type_: Type, code: Option<Statement>) -> Self{
         Declaration {
             name,
+            doc: None,
             type_,
             value: None,
-            code: code.map(|x| Box::new(x)),
+            code: code.map(Box::new),
             next: None,
         }
     }
 
-    pub fn attach_most_next(&mut self, declaration: Declaration) {
-        match &mut self.next {
-            None => self.next = Some(Box::new(declaration)),
-            Some(nexter) => nexter.attach_most_next(declaration),
+    /// Creates a declaration standing for one that could not be parsed.
+    pub fn new_error() -> Self {
+        Declaration {
+            name: String::new(),
+            doc: None,
+            type_: Type {kind: TypeKind::Error, subtype: None, size: None, param_list: None},
+            value: None,
+            code: None,
+            next: None,
         }
     }
+
+    /// Links the declarations in order and returns the first one. Each
+    /// declaration is moved once, however long the program is.
+    pub fn link(declarations: Vec<Declaration>) -> Option<Declaration> {
+        declarations.into_iter().rev().fold(None, |next, mut declaration| {
+            declaration.next = next.map(Box::new);
+            Some(declaration)
+        })
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -132,6 +250,8 @@ pub struct Statement {
     pub expression: Option<Expression>,
     pub for_initial_expr: Option<Expression>,
     pub for_next_expr: Option<Expression>,
+    /// Expressions printed by a print statement.
+    pub print_args: Vec<Expression>,
     pub body: Option<Box<Statement>>,
     pub else_body: Option<Box<Statement>>,
     pub next_statement: Option<Box<Statement>>,
@@ -144,17 +264,19 @@ impl Statement {
                   expression: None,
                   for_initial_expr: None,
                   for_next_expr: None,
+                  print_args: Vec::new(),
                   body: None,
                   else_body: None,
                   next_statement: None}
     }
 
     pub fn new_expression(expression: Expression) -> Self {
-        Statement{kind: StatementKind::Declaration,
+        Statement{kind: StatementKind::Expression,
                   declaration: None,
                   expression: Some(expression),
                   for_initial_expr: None,
                   for_next_expr: None,
+                  print_args: Vec::new(),
                   body: None,
                   else_body: None,
                   next_statement: None}
@@ -166,6 +288,19 @@ impl Statement {
                   expression: condition,
                   for_initial_expr: initial_expression,
                   for_next_expr: next_expression,
+                  print_args: Vec::new(),
+                  body: Some(Box::new(body)),
+                  else_body: None,
+                  next_statement: None}
+    }
+
+    pub fn new_while(condition: Expression, body: Statement) -> Self {
+        Statement{kind: StatementKind::While,
+                  declaration: None,
+                  expression: Some(condition),
+                  for_initial_expr: None,
+                  for_next_expr: None,
+                  print_args: Vec::new(),
                   body: Some(Box::new(body)),
                   else_body: None,
                   next_statement: None}
@@ -177,17 +312,19 @@ impl Statement {
                   expression: Some(condition),
                   for_initial_expr: None,
                   for_next_expr: None,
+                  print_args: Vec::new(),
                   body: Some(Box::new(if_body)),
-                  else_body: else_body.map(|x| Box::new(x)),
+                  else_body: else_body.map(Box::new),
                   next_statement: None}
     }
 
-    pub fn new_print(expression: Option<Expression>) -> Self {
+    pub fn new_print(print_args: Vec<Expression>) -> Self {
         Statement{kind: StatementKind::Print,
                   declaration: None,
-                  expression: expression,
+                  expression: None,
                   for_initial_expr: None,
                   for_next_expr: None,
+                  print_args,
                   body: None,
                   else_body: None,
                   next_statement: None}
@@ -199,36 +336,61 @@ impl Statement {
                   expression: Some(expression),
                   for_initial_expr: None,
                   for_next_expr: None,
+                  print_args: Vec::new(),
                   body: None,
                   else_body: None,
                   next_statement: None}
     }
+    pub fn new_error() -> Self {
+        Statement{kind: StatementKind::Error,
+                  declaration: None,
+                  expression: None,
+                  for_initial_expr: None,
+                  for_next_expr: None,
+                  print_args: Vec::new(),
+                  body: None,
+                  else_body: None,
+                  next_statement: None}
+    }
+
     pub fn new_block(block: Option<Statement>) -> Self {
         Statement{kind: StatementKind::Block,
                   declaration: None,
                   expression: None,
                   for_initial_expr: None,
                   for_next_expr: None,
+                  print_args: Vec::new(),
                   body: None,
                   else_body: None,
-                  next_statement: block.map(|x| Box::new(x))}
+                  next_statement: block.map(Box::new)}
     }
-    pub fn attach_most_next(&mut self, statement: Statement) {
-        match &mut self.next_statement {
-            None => self.next_statement = Some(Box::new(statement)),
-            Some(next) => next.attach_most_next(statement),
-        }
+
+    /// Links the statements in order and returns the first one. A statement
+    /// is followed by the next one after the statements already linked to it,
+    /// such as the contents of a block.
+    pub fn link(statements: Vec<Statement>) -> Option<Statement> {
+        statements.into_iter().rev().fold(None, |next, mut statement| {
+            let mut last = &mut statement;
+            while last.next_statement.is_some() {
+                last = last.next_statement.as_deref_mut().unwrap();
+            }
+            last.next_statement = next.map(Box::new);
+            Some(statement)
+        })
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum ExpressionValue {
     Name(String),
-    Integer(usize),
+    Integer(i64),
     Float(f64),
+    Boolean(bool),
     Character(char),
     Text(String),
     Array(Vec<Expression>),
+    /// Arguments of a function call in the order they were passed.
+    Arguments(Vec<Expression>),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -254,6 +416,26 @@ impl Expression {
             value: Some(value),
         }
     }
+
+    pub fn new_binary(kind: ExpressionKind, left: Expression, right: Expression) -> Expression {
+        Expression {
+            kind,
+            left: Some(Box::new(left)),
+            right: Some(Box::new(right)),
+            value: None,
+        }
+    }
+
+    /// Creates a prefix or postfix expression with the operand on the left.
+    pub fn new_unary(kind: ExpressionKind, operand: Expression) -> Expression {
+        Expression {
+            kind,
+            left: Some(Box::new(operand)),
+            right: None,
+            value: None,
+        }
+    }
+
     pub fn attach_leftmost(&mut self, expr: Expression) {
         match &mut self.left {
             None => {
@@ -264,14 +446,118 @@ impl Expression {
             }
         }
     }
-    pub fn attach_rightmost(&mut self, expr: Expression) {
-        match &mut self.right {
-            None => {
-                self.right = Some(Box::new(expr));
+}
+
+/// Writes the expression back as source code with every unary and binary
+/// expression enclosed in parentheses, e.g. `((a - b) - c)`.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let left = self.left.as_deref();
+        let right = self.right.as_deref();
+        match (&self.kind, left, right) {
+            (ExpressionKind::Minus, Some(operand), None)
+            | (ExpressionKind::Negation, Some(operand), None) => {
+                write!(f, "({}{})", self.kind.operator(), operand)
             }
-            Some(next_expr) => {
-                next_expr.attach_rightmost(expr);
+            (ExpressionKind::Incrementation, Some(operand), None)
+            | (ExpressionKind::Decrementation, Some(operand), None) => {
+                write!(f, "({}{})", operand, self.kind.operator())
+            }
+            (ExpressionKind::Subscript, Some(array), Some(index)) => write!(f, "{}[{}]", array, index),
+            (ExpressionKind::FunctionCall, Some(function), None) => match &self.value {
+                Some(arguments) => write!(f, "{}({})", function, arguments),
+                None => write!(f, "{}()", function),
+            },
+            (_, Some(left), Some(right)) => {
+                write!(f, "({} {} {})", left, self.kind.operator(), right)
+            }
+            _ => match &self.value {
+                Some(value) => write!(f, "{}", value),
+                None => write!(f, "{:?}", self.kind),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ExpressionValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpressionValue::Name(name) => write!(f, "{}", name),
+            ExpressionValue::Integer(integer) => write!(f, "{}", integer),
+            ExpressionValue::Float(float) => write!(f, "{:?}", float),
+            ExpressionValue::Boolean(boolean) => write!(f, "{}", boolean),
+            ExpressionValue::Character(character) => write!(f, "'{}'", escape(&character.to_string())),
+            ExpressionValue::Text(text) => write!(f, "\"{}\"", escape(text)),
+            ExpressionValue::Array(values) => {
+                let values: Vec<String> = values.iter().map(Expression::to_string).collect();
+                write!(f, "{{{}}}", values.join(", "))
+            }
+            ExpressionValue::Arguments(arguments) => {
+                let arguments: Vec<String> = arguments.iter().map(Expression::to_string).collect();
+                write!(f, "{}", arguments.join(", "))
             }
         }
     }
 }
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' | '\'' | '"' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn float_arithmetic_and_comparison() {
+        let float = TypeKind::Float;
+        let integer = TypeKind::Integer;
+        let addition = ExpressionKind::Addition;
+        assert_eq!(addition.binary_result_type(&float, &float), Some(TypeKind::Float));
+        assert_eq!(addition.binary_result_type(&float, &integer), None);
+        assert_eq!(ExpressionKind::Modulo.binary_result_type(&float, &float), None);
+        let less = ExpressionKind::Less;
+        assert_eq!(less.binary_result_type(&float, &float), Some(TypeKind::Boolean));
+        assert_eq!(less.binary_result_type(&integer, &float), None);
+        let equal = ExpressionKind::Equal;
+        assert_eq!(equal.binary_result_type(&float, &float), Some(TypeKind::Boolean));
+        assert_eq!(equal.binary_result_type(&integer, &integer), Some(TypeKind::Boolean));
+        assert_eq!(equal.binary_result_type(&float, &integer), None);
+        let not_equal = ExpressionKind::NotEqual;
+        assert_eq!(not_equal.binary_result_type(&float, &float), Some(TypeKind::Boolean));
+        assert_eq!(ExpressionKind::Minus.unary_result_type(&float), Some(TypeKind::Float));
+        assert_eq!(ExpressionKind::Negation.unary_result_type(&float), None);
+    }
+
+    #[test]
+    fn link_statements_after_block_contents() {
+        let block = Statement::new_block(Some(Statement::new_print(Vec::new())));
+        let statements = vec![Statement::new_error(), block, Statement::new_error()];
+        let mut kinds = Vec::new();
+        let mut next = Statement::link(statements);
+        while let Some(statement) = next {
+            kinds.push(statement.kind);
+            next = statement.next_statement.map(|statement| *statement);
+        }
+        let expected = [
+            StatementKind::Error,
+            StatementKind::Block,
+            StatementKind::Print,
+            StatementKind::Error,
+        ];
+        assert_eq!(kinds, expected);
+        assert_eq!(Declaration::link(Vec::new()), None);
+    }
+}
@@ -4,6 +4,7 @@ use clap::{Arg, App, SubCommand};
 extern crate rbm;
 use rbm::scanner;
 use rbm::parser;
+use rbm::source::SourceMap;
 
 fn main() {
     let matches = App::new("rbm")
@@ -13,12 +14,35 @@ fn main() {
                     .arg(Arg::with_name("file")
                          .required(true)
                          )
+                    .arg(Arg::with_name("floats")
+                         .long("floats")
+                         .help("Enables the float type extension")
+                         )
+                    .arg(Arg::with_name("nested-comments")
+                         .long("nested-comments")
+                         .help("Enables the nested block comments extension")
+                         )
+                    .arg(Arg::with_name("format")
+                         .long("format")
+                         .takes_value(true)
+                         .possible_values(&["table", "json", "csv"])
+                         .default_value("table")
+                         .help("Format in which tokens and errors are printed")
+                         )
                     )
         .subcommand(SubCommand::with_name("parse")
                     .about("Parses specified source file")
                     .arg(Arg::with_name("file")
                          .required(true)
                          )
+                    .arg(Arg::with_name("floats")
+                         .long("floats")
+                         .help("Enables the float type extension")
+                         )
+                    .arg(Arg::with_name("nested-comments")
+                         .long("nested-comments")
+                         .help("Enables the nested block comments extension")
+                         )
                     ).get_matches();
     if matches.is_present("lex") {
         let smatches = matches.subcommand_matches("lex").unwrap();
@@ -29,12 +53,13 @@ fn main() {
             return;
         }
         let content = fs::read_to_string(source_file_path).unwrap();
-        let tokens = scanner::tokenize(&content);
-        if tokens.is_ok() {
-            scanner::print_pretty(&tokens.unwrap());
-        } else {
-            print!("{:?}", tokens.unwrap_err());
-        }
+        let extensions = scanner::Extensions {
+            floats: smatches.is_present("floats"),
+            nested_comments: smatches.is_present("nested-comments"),
+        };
+        let format = smatches.value_of("format").unwrap().parse().unwrap();
+        let (tokens, errors) = scanner::tokenize_recovering(&content, extensions);
+        print!("{}", scanner::dump(&tokens, &errors, format));
     } else if matches.is_present("parse") {
         let smatches = matches.subcommand_matches("parse").unwrap();
         let source_file_str = smatches.value_of("file").unwrap();
@@ -44,19 +69,21 @@ fn main() {
             return;
         }
         let content = fs::read_to_string(source_file_path).unwrap();
-        let tokens_result = scanner::tokenize(&content);
-        match tokens_result {
-            Ok(tokens) => {
-                let ast = parser::parse(&tokens);
-                if ast.is_err() {
-                    println!("{:?}", ast.unwrap_err());
-                } else {
-                    dbg!(ast.unwrap());
-                }
-            },
-            Err(err) => {
-                println!("{:?}", err);
-            }
+        let extensions = scanner::Extensions {
+            floats: smatches.is_present("floats"),
+            nested_comments: smatches.is_present("nested-comments"),
+        };
+        let source_map = SourceMap::new(&content);
+        let (tokens, errors) = scanner::tokenize_with_trivia(&content, extensions);
+        for err in errors {
+            println!("{}\n{}", err, source_map.underline(err.span()));
+        }
+        let (ast, errors) = parser::parse_with_trivia_recovering(&tokens);
+        for err in errors {
+            println!("{}\n{}", err, source_map.underline(err.span()));
+        }
+        if let Some(ast) = ast {
+            dbg!(ast);
         }
     } else {
         print!("{}", matches.usage());
@@ -1,34 +1,45 @@
 use crate::ast::{
-    Declaration, Expression, ExpressionKind, ExpressionValue, Statement, Type,
+    Declaration, Expression, ExpressionKind, ExpressionValue, ParameterList, Statement, Type,
     TypeKind,
 };
-use crate::scanner::{Category, Token};
+use crate::scanner::{self, Category, ScanError, Token, TriviaToken};
+use crate::source::Span;
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt;
 
 #[derive(Debug)]
-pub enum ParseError {
+pub enum ParseError<'src> {
+    Scan(ScanError),
     UnexpectedEndOfTokens {
         line: usize,
         column: usize,
+        span: Span,
     },
     UnexpectedToken {
-        unexpected: Token,
-        expected: Vec<Category>,
-    },
-    ExpectedButMissingToken {
-        after: Token,
+        unexpected: Token<'src>,
         expected: Vec<Category>,
+        context: &'static str,
     },
     UnknownTypeIdentifier {
-        token: Token,
+        token: Token<'src>,
+    },
+    FloatsNotEnabled {
+        token: Token<'src>,
+    },
+    IntegerOutOfRange {
+        token: Token<'src>,
+    },
+    InvalidArraySize {
+        token: Token<'src>,
     },
 }
 
-impl fmt::Display for ParseError {
+impl fmt::Display for ParseError<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::UnexpectedEndOfTokens { line, column } => write!(
+            ParseError::Scan(error) => write!(f, "{}", error),
+            ParseError::UnexpectedEndOfTokens { line, column, .. } => write!(
                 f,
                 "Unexpected end of tokens after token at line {} column {}.",
                 line, column
@@ -36,705 +47,803 @@ impl fmt::Display for ParseError {
             ParseError::UnexpectedToken {
                 unexpected,
                 expected,
-            } => write!(
-                f,
-                "Unexpected token at line {} column {}. Found {}, expected {:?}",
-                unexpected.line, unexpected.column, unexpected.category, expected
-            ),
-            ParseError::ExpectedButMissingToken { after, expected } => write!(
+                context,
+            } => {
+                write!(f, "Unexpected {}, expected {}", unexpected, describe_expected(expected))?;
+                if !context.is_empty() {
+                    write!(f, " {}", context)?;
+                }
+                Ok(())
+            }
+            ParseError::FloatsNotEnabled { token } => write!(
                 f,
-                "Expected one of {:?} after {} at line {} column {}",
-                expected, after.category, after.line, after.column
+                "Type float at line {} column {} requires the float extension",
+                token.line, token.column
             ),
             ParseError::UnknownTypeIdentifier { token } => write!(
                 f,
                 "Type identifier {} at line {} column {} is unknown",
                 token.lexeme, token.line, token.column
             ),
+            ParseError::IntegerOutOfRange { token } => write!(
+                f,
+                "Integer literal {} at line {} column {} does not fit in 64 bits",
+                token.lexeme, token.line, token.column
+            ),
+            ParseError::InvalidArraySize { token } => write!(
+                f,
+                "Array size at line {} column {} must be a positive integer",
+                token.line, token.column
+            ),
         }
     }
 }
 
-impl Error for ParseError {}
+impl ParseError<'_> {
+    /// Line and column at which the error occurred.
+    pub fn location(&self) -> (usize, usize) {
+        match self {
+            ParseError::Scan(error) => error.location(),
+            ParseError::UnexpectedEndOfTokens { line, column, .. } => (*line, *column),
+            ParseError::UnexpectedToken { unexpected: token, .. }
+            | ParseError::UnknownTypeIdentifier { token }
+            | ParseError::FloatsNotEnabled { token }
+            | ParseError::IntegerOutOfRange { token }
+            | ParseError::InvalidArraySize { token } => (token.line, token.column),
+        }
+    }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
-struct TokenStream {
-    tokens: Vec<Token>,
-    current_index: usize,
+    /// Source range the error refers to, the last token for an unexpected
+    /// end of tokens.
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::Scan(error) => error.span(),
+            ParseError::UnexpectedEndOfTokens { span, .. } => *span,
+            ParseError::UnexpectedToken { unexpected: token, .. }
+            | ParseError::UnknownTypeIdentifier { token }
+            | ParseError::FloatsNotEnabled { token }
+            | ParseError::IntegerOutOfRange { token }
+            | ParseError::InvalidArraySize { token } => token.span,
+        }
+    }
 }
 
-impl TokenStream {
-    pub fn new(tokens: &[Token]) -> Self {
+impl Error for ParseError<'_> {}
+
+/// Lists the expected categories as "a", "a or b" or "one of a, b or c",
+/// naming whole FIRST sets instead of listing their members.
+fn describe_expected(expected: &[Category]) -> String {
+    let sets: [(&[Category], &str); 3] = [
+        (&STATEMENT_START, "a statement"),
+        (&EXPRESSION_START, "an expression"),
+        (&TYPE_START, "a type"),
+    ];
+    let mut remaining = expected.to_vec();
+    let mut set_names = Vec::new();
+    for (set, name) in sets.iter() {
+        if set.iter().all(|category| remaining.contains(category)) {
+            remaining.retain(|category| !set.contains(category));
+            set_names.push(String::from(*name));
+        }
+    }
+    let mut names: Vec<String> = remaining.iter().map(|category| category.to_string()).collect();
+    names.extend(set_names);
+    match names.as_slice() {
+        [] => String::from("nothing"),
+        [name] => name.clone(),
+        [first, second] => format!("{} or {}", first, second),
+        [init @ .., last] => format!("one of {} or {}", init.join(", "), last),
+    }
+}
+
+/// FIRST(<EXPRESSION>) from grammar.md.
+const EXPRESSION_START: [Category; 10] = [
+    Category::Identifier,
+    Category::Integer,
+    Category::Float,
+    Category::Boolean,
+    Category::Character,
+    Category::Text,
+    Category::Minus,
+    Category::Exclamation,
+    Category::OpenParen,
+    Category::OpenBrace,
+];
+
+/// FIRST(<STATEMENT>) from grammar.md. A statement starting with '{' is
+/// always a block, never an array literal.
+const STATEMENT_START: [Category; 15] = [
+    Category::Identifier,
+    Category::Integer,
+    Category::Float,
+    Category::Boolean,
+    Category::Character,
+    Category::Text,
+    Category::Minus,
+    Category::Exclamation,
+    Category::OpenParen,
+    Category::OpenBrace,
+    Category::IfKeyword,
+    Category::ForKeyword,
+    Category::WhileKeyword,
+    Category::PrintKeyword,
+    Category::ReturnKeyword,
+];
+
+/// FIRST(<FULL_TYPE>) and FIRST(<EMPTY_TYPE>) from grammar.md.
+const TYPE_START: [Category; 8] = [
+    Category::VoidKeyword,
+    Category::BooleanKeyword,
+    Category::CharKeyword,
+    Category::IntegerKeyword,
+    Category::StringKeyword,
+    Category::FloatKeyword,
+    Category::ArrayKeyword,
+    Category::FunctionKeyword,
+];
+
+/// Lazily pulls tokens out of the underlying iterator, keeping only the
+/// lookahead needed by the parser. Each token may come with the documentation
+/// written before it. The first scanning error stops the stream and is kept so
+/// it can be reported instead of the parsing error it causes. When
+/// recovering, the errors the parser recovered from are collected here along
+/// with the braces and parentheses left open by the consumed tokens.
+struct TokenStream<'src> {
+    tokens: Box<dyn Iterator<Item = Result<DocumentedToken<'src>, ScanError>> + 'src>,
+    lookahead: VecDeque<DocumentedToken<'src>>,
+    previous: Option<Token<'src>>,
+    consumed: usize,
+    open_braces: usize,
+    open_parens: usize,
+    error: Option<ScanError>,
+    recovering: bool,
+    errors: Vec<ParseError<'src>>,
+}
+
+type DocumentedToken<'src> = (Token<'src>, Option<String>);
+
+impl<'src> TokenStream<'src> {
+    pub fn new<I>(tokens: I) -> Self
+    where
+        I: Iterator<Item = Result<DocumentedToken<'src>, ScanError>> + 'src,
+    {
         TokenStream {
-            tokens: tokens.to_vec(),
-            current_index: 0,
+            tokens: Box::new(tokens),
+            lookahead: VecDeque::new(),
+            previous: None,
+            consumed: 0,
+            open_braces: 0,
+            open_parens: 0,
+            error: None,
+            recovering: false,
+            errors: Vec::new(),
+        }
+    }
+
+    fn fill(&mut self, amount: usize) {
+        while self.error.is_none() && self.lookahead.len() < amount {
+            match self.tokens.next() {
+                Some(Ok(token)) => self.lookahead.push_back(token),
+                Some(Err(error)) => self.error = Some(error),
+                None => break,
+            }
         }
     }
 
     pub fn consume(&mut self, amount: usize) {
-        self.current_index += amount;
+        for _ in 0..amount {
+            self.next();
+        }
     }
 
-    pub fn next(&mut self) -> Option<Token> {
-        let token = self.tokens.get(self.current_index)?;
-        self.current_index += 1;
-        Some(token.clone())
+    pub fn next(&mut self) -> Option<Token<'src>> {
+        self.fill(1);
+        let (token, _) = self.lookahead.pop_front()?;
+        self.consumed += 1;
+        match token.category {
+            Category::OpenBrace => self.open_braces += 1,
+            Category::CloseBrace => self.open_braces = self.open_braces.saturating_sub(1),
+            Category::OpenParen => self.open_parens += 1,
+            Category::CloseParen => self.open_parens = self.open_parens.saturating_sub(1),
+            _ => (),
+        }
+        self.previous = Some(token.clone());
+        Some(token)
     }
 
-    pub fn peek(&mut self, n_ahead: usize) -> Option<Token> {
-        let token = self.tokens.get(self.current_index + n_ahead - 1)?;
+    pub fn peek(&mut self, n_ahead: usize) -> Option<Token<'src>> {
+        self.fill(n_ahead);
+        let (token, _) = self.lookahead.get(n_ahead - 1)?;
         Some(token.clone())
     }
 
-    pub fn peek_previous(&self) -> Option<Token> {
-        let token = self.tokens.get(self.current_index - 1)?;
-        Some(token.clone())
+    pub fn peek_previous(&self) -> Option<Token<'src>> {
+        self.previous.clone()
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.current_index == self.tokens.len()
+    pub fn is_empty(&mut self) -> bool {
+        self.peek(1).is_none()
     }
 
-    pub fn remain(&self) -> usize {
-        if self.is_empty() {
-            0
-        } else {
-            self.tokens.len() - self.current_index
+    pub fn take_doc(&mut self) -> Option<String> {
+        self.fill(1);
+        self.lookahead.front_mut()?.1.take()
+    }
+
+    /// Returns the number of tokens consumed so far.
+    pub fn consumed(&self) -> usize {
+        self.consumed
+    }
+
+    /// Returns the last token pulled out of the underlying iterator.
+    pub fn peek_last(&self) -> Option<Token<'src>> {
+        match self.lookahead.back() {
+            Some((token, _)) => Some(token.clone()),
+            None => self.peek_previous(),
         }
     }
+
+    pub fn take_error(&mut self) -> Option<ScanError> {
+        self.error.take()
+    }
 }
 
-fn error_on_empty_stream(stream: &TokenStream) -> Result<(), ParseError> {
-    if stream.remain() == 0 {
-        let last = stream.peek_previous().unwrap();
-        let (line, column) = (last.line, last.column);
-        return Err(ParseError::UnexpectedEndOfTokens { line, column });
+fn end_of_tokens<'src>(stream: &mut TokenStream<'src>) -> ParseError<'src> {
+    if let Some(error) = stream.take_error() {
+        return ParseError::Scan(error);
+    }
+    let (line, column, span) = stream
+        .peek_last()
+        .map_or((1, 1, Span::default()), |last| (last.line, last.column, last.span));
+    ParseError::UnexpectedEndOfTokens { line, column, span }
+}
+
+fn error_on_empty_stream<'src>(stream: &mut TokenStream<'src>) -> Result<(), ParseError<'src>> {
+    if stream.is_empty() {
+        return Err(end_of_tokens(stream));
     }
     Ok(())
 }
 
-fn parse_token(
-    category: Category,
-    stream: &mut TokenStream,
-) -> Result<(String, Category), ParseError> {
-    error_on_empty_stream(stream)?;
-    let token = stream.peek(1);
-    let expected = [category].to_vec();
-    if token.is_none() {
-        let after = stream.peek_previous().unwrap();
-        return Err(ParseError::ExpectedButMissingToken { after, expected });
+/// Returns the token `n_ahead` tokens ahead or an error if the tokens end
+/// before it.
+fn peek_token<'src>(
+    stream: &mut TokenStream<'src>,
+    n_ahead: usize,
+) -> Result<Token<'src>, ParseError<'src>> {
+    match stream.peek(n_ahead) {
+        Some(token) => Ok(token),
+        None => Err(end_of_tokens(stream)),
+    }
+}
+
+fn next_token<'src>(stream: &mut TokenStream<'src>) -> Result<Token<'src>, ParseError<'src>> {
+    match stream.next() {
+        Some(token) => Ok(token),
+        None => Err(end_of_tokens(stream)),
     }
-    let token = token.unwrap();
+}
+
+/// Reports the next token as unexpected where one of `expected` should be.
+/// The context completes the message, e.g. "after expression statement".
+fn unexpected_token<'src>(
+    expected: &[Category],
+    context: &'static str,
+    stream: &mut TokenStream<'src>,
+) -> ParseError<'src> {
+    match peek_token(stream, 1) {
+        Ok(unexpected) => ParseError::UnexpectedToken {
+            unexpected,
+            expected: expected.to_vec(),
+            context,
+        },
+        Err(error) => error,
+    }
+}
+
+fn parse_token<'src>(
+    category: Category,
+    context: &'static str,
+    stream: &mut TokenStream<'src>,
+) -> Result<(String, Category), ParseError<'src>> {
+    let token = peek_token(stream, 1)?;
     match token.category {
         x if x == category => {
             stream.consume(1);
-            return Ok((token.lexeme, token.category));
+            Ok((String::from(token.lexeme), token.category))
         }
-        _ => Err(ParseError::UnexpectedToken {
-            unexpected: token,
-            expected,
-        }),
+        _ => Err(unexpected_token(&[category], context, stream)),
     }
 }
 
-pub fn parse(tokens: &[Token]) -> Result<Declaration, ParseError> {
-    if tokens.is_empty() {
+pub fn parse<'src>(tokens: &'src [Token<'src>]) -> Result<Declaration, ParseError<'src>> {
+    parse_tokens(tokens.iter().cloned().map(Ok))
+}
+
+/// Parses tokens produced by [`scanner::tokenize_with_trivia`], attaching doc
+/// comments to the declarations following them.
+pub fn parse_with_trivia<'src>(
+    tokens: &'src [TriviaToken<'src>],
+) -> Result<Declaration, ParseError<'src>> {
+    let tokens = tokens
+        .iter()
+        .filter(|token| token.token.category != Category::EndOfFile)
+        .map(|token| Ok((token.token.clone(), scanner::doc_text(&token.leading))));
+    parse_documented_tokens(tokens)
+}
+
+/// Parses tokens as they are produced by the iterator, e.g. a
+/// [`scanner::Lexer`], without collecting them first.
+pub fn parse_tokens<'src, I>(tokens: I) -> Result<Declaration, ParseError<'src>>
+where
+    I: Iterator<Item = Result<Token<'src>, ScanError>> + 'src,
+{
+    parse_documented_tokens(tokens.map(|token| token.map(|token| (token, None))))
+}
+
+/// Parses the tokens reporting every error instead of only the first one.
+/// After an error the parser skips tokens up to the end of the statement or
+/// the next top-level declaration and leaves an error node in its place.
+/// Returns the declarations parsed this way together with all the errors.
+pub fn parse_recovering<'src>(
+    tokens: &'src [Token<'src>],
+) -> (Option<Declaration>, Vec<ParseError<'src>>) {
+    let mut token_stream = TokenStream::new(tokens.iter().cloned().map(|token| Ok((token, None))));
+    parse_program_recovering(&mut token_stream)
+}
+
+/// Recovering counterpart of [`parse_with_trivia`].
+pub fn parse_with_trivia_recovering<'src>(
+    tokens: &'src [TriviaToken<'src>],
+) -> (Option<Declaration>, Vec<ParseError<'src>>) {
+    let tokens = tokens
+        .iter()
+        .filter(|token| token.token.category != Category::EndOfFile)
+        .map(|token| Ok((token.token.clone(), scanner::doc_text(&token.leading))));
+    let mut token_stream = TokenStream::new(tokens);
+    parse_program_recovering(&mut token_stream)
+}
+
+fn parse_documented_tokens<'src, I>(tokens: I) -> Result<Declaration, ParseError<'src>>
+where
+    I: Iterator<Item = Result<DocumentedToken<'src>, ScanError>> + 'src,
+{
+    let mut token_stream = TokenStream::new(tokens);
+    if token_stream.is_empty() {
+        if let Some(error) = token_stream.take_error() {
+            return Err(ParseError::Scan(error));
+        }
         return Ok(Declaration {
                 name: String::from("Empty file"),
-                type_: Type{kind: TypeKind::Text, subtype: None, param_list: None},
+                doc: None,
+                type_: Type{kind: TypeKind::Text, subtype: None, size: None, param_list: None},
                 value: None,
                 code: None,
                 next: None,
         });
     }
-    let mut token_stream = TokenStream::new(tokens);
-    parse_program(&mut token_stream)
+    let result = parse_program(&mut token_stream);
+    match token_stream.take_error() {
+        Some(error) => Err(ParseError::Scan(error)),
+        None => result,
+    }
 }
 
-fn parse_program(stream: &mut TokenStream) -> Result<Declaration, ParseError> {
-    let mut first_declaration = parse_declaration(stream)?;
+fn parse_program<'src>(stream: &mut TokenStream<'src>) -> Result<Declaration, ParseError<'src>> {
+    let mut declarations = vec![parse_declaration(stream)?];
     while !stream.is_empty() {
         match parse_declaration(stream) {
             Err(x) => {
                 return Err(x);
             }
-            Ok(declaration) => first_declaration.attach_most_next(declaration),
+            Ok(declaration) => declarations.push(declaration),
         }
     }
-    Ok(first_declaration)
+    Ok(Declaration::link(declarations).unwrap())
+}
+
+fn parse_program_recovering<'src>(
+    stream: &mut TokenStream<'src>,
+) -> (Option<Declaration>, Vec<ParseError<'src>>) {
+    stream.recovering = true;
+    let mut declarations = Vec::new();
+    while !stream.is_empty() {
+        let consumed = stream.consumed();
+        stream.open_braces = 0;
+        stream.open_parens = 0;
+        let declaration = match parse_declaration(stream) {
+            Ok(declaration) => declaration,
+            Err(error) => {
+                stream.errors.push(error);
+                if stream.consumed() == consumed {
+                    stream.consume(1);
+                }
+                synchronize_declaration(stream);
+                Declaration::new_error()
+            }
+        };
+        declarations.push(declaration);
+    }
+    (Declaration::link(declarations), std::mem::take(&mut stream.errors))
 }
 
-fn parse_declaration(stream: &mut TokenStream) -> Result<Declaration, ParseError> {
+/// Skips tokens up to the beginning of the next top-level declaration, that
+/// is an identifier followed by a colon outside of the braces and parentheses
+/// opened since the failed declaration started, where parameters and local
+/// declarations look the same.
+fn synchronize_declaration(stream: &mut TokenStream) {
+    while let Some(token) = stream.peek(1) {
+        let outside = stream.open_braces == 0 && stream.open_parens == 0;
+        match token.category {
+            // a semicolon outside of braces ends the declaration even when a
+            // parenthesis was left open in it
+            Category::Semicolon if stream.open_braces == 0 => stream.open_parens = 0,
+            Category::Identifier if outside && is_declaration_start(stream) => return,
+            _ => (),
+        }
+        stream.consume(1);
+    }
+}
+
+fn is_declaration_start(stream: &mut TokenStream) -> bool {
+    stream.peek(2).map(|token| token.category) == Some(Category::Colon)
+}
+
+/// Skips tokens up to the end of the current statement, i.e. past the next
+/// semicolon or nested block, or up to the brace closing the enclosing block.
+fn synchronize_statement(stream: &mut TokenStream) {
+    let mut depth = 0;
+    while let Some(token) = stream.peek(1) {
+        match token.category {
+            Category::Semicolon if depth == 0 => {
+                stream.consume(1);
+                return;
+            }
+            Category::CloseBrace if depth == 0 => return,
+            Category::CloseBrace if depth == 1 => {
+                stream.consume(1);
+                return;
+            }
+            Category::CloseBrace => depth -= 1,
+            Category::OpenBrace => depth += 1,
+            _ => (),
+        }
+        stream.consume(1);
+    }
+}
+
+fn parse_declaration<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Declaration, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    let name = parse_token(Category::Identifier, stream)?.0;
-    parse_token(Category::Colon, stream)?;
-    match stream.peek(1).unwrap().category {
+    let doc = stream.take_doc();
+    let name = parse_token(Category::Identifier, "at start of declaration", stream)?.0;
+    parse_token(Category::Colon, "after declaration name", stream)?;
+    let mut declaration = match peek_token(stream, 1)?.category {
         // parse variable or array declaration
-        Category::Identifier => {
+        category if is_type_name(category) => {
             let type_ = parse_full_type(stream)?;
             let value = parse_variable_assignment(stream)?;
-            return Ok(Declaration::new_value(name, type_, value));
+            Declaration::new_value(name, type_, value)
         }
         Category::ArrayKeyword => {
             let type_ = parse_full_type(stream)?;
             let value = parse_variable_assignment(stream)?;
-            return Ok(Declaration::new_value(name, type_, value));
+            Declaration::new_value(name, type_, value)
         }
         Category::FunctionKeyword => {
             let type_ = parse_full_type(stream)?;
             let code = parse_function_assignment(stream)?;
-            return Ok(Declaration::new_function(name, type_, code));
-        }
-        _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [
-                Category::Identifier,
-                Category::ArrayKeyword,
-                Category::FunctionKeyword,
-            ]
-            .to_vec();
-            return Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            });
+            Declaration::new_function(name, type_, code)
         }
-    }
+        _ => return Err(unexpected_token(&TYPE_START, "after ':'", stream)),
+    };
+    declaration.doc = doc;
+    Ok(declaration)
 }
 
-fn parse_variable_assignment(stream: &mut TokenStream) -> Result<Option<Expression>, ParseError> {
+fn parse_variable_assignment<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Option<Expression>, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
+    match peek_token(stream, 1)?.category {
         // parse variable or array declaration
         Category::Equal => {
-            parse_token(Category::Equal, stream)?;
+            stream.consume(1);
             let expression = parse_expression(stream)?;
-            parse_token(Category::Semicolon, stream)?;
-            return Ok(Some(expression));
+            parse_token(Category::Semicolon, "after variable declaration", stream)?;
+            Ok(Some(expression))
         }
         Category::Semicolon => {
-            parse_token(Category::Semicolon, stream)?;
-            return Ok(None);
-        }
-        _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [Category::Equal, Category::Semicolon].to_vec();
-            return Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            });
+            stream.consume(1);
+            Ok(None)
         }
+        _ => Err(unexpected_token(
+            &[Category::Equal, Category::Semicolon],
+            "after variable type",
+            stream,
+        )),
     }
 }
 
-fn parse_function_assignment(stream: &mut TokenStream) -> Result<Option<Statement>, ParseError> {
+fn parse_function_assignment<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Option<Statement>, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
+    match peek_token(stream, 1)?.category {
         // parse variable or array declaration
         Category::Equal => {
-            parse_token(Category::Equal, stream)?;
+            stream.consume(1);
             let block = parse_block_statement(stream)?;
-            return Ok(Some(block));
+            Ok(Some(block))
         }
         Category::Semicolon => {
-            parse_token(Category::Semicolon, stream)?;
-            return Ok(None);
-        }
-        _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [Category::Equal, Category::Semicolon].to_vec();
-            return Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            });
+            stream.consume(1);
+            Ok(None)
         }
+        _ => Err(unexpected_token(
+            &[Category::Equal, Category::Semicolon],
+            "after function type",
+            stream,
+        )),
     }
 }
 
-fn parse_statement(stream: &mut TokenStream) -> Result<Statement, ParseError> {
+fn parse_statement<'src>(stream: &mut TokenStream<'src>) -> Result<Statement, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    let one_ahead = stream.peek(1).unwrap();
-    match one_ahead.category {
-        Category::Identifier => {
-            // if starts with identifier might be declaration/expression
-            let two_ahead = stream.peek(2).unwrap();
-            match two_ahead.category {
-                //Category::Equal => {
-                //// assignment
-                //parse_assignment_statement(stream)
-                //}
-                Category::Colon => {
-                    // declaration of variable
-                    Ok(Statement::new_declaration(parse_declaration(stream)?))
-                }
-                _ => {
-                    // it is probably an expression
-                    let expression = parse_expression(stream)?;
-                    parse_token(Category::Semicolon, stream)?;
-                    Ok(Statement::new_expression(expression))
-                }
-            }
+    match peek_token(stream, 1)?.category {
+        // if starts with identifier might be declaration/expression
+        Category::Identifier if peek_token(stream, 2)?.category == Category::Colon => {
+            // declaration of variable
+            Ok(Statement::new_declaration(parse_declaration(stream)?))
         }
         Category::IfKeyword => parse_if_else_statement(stream),
         Category::ForKeyword => parse_for_statement(stream),
+        Category::WhileKeyword => parse_while_statement(stream),
         Category::PrintKeyword => parse_print_statement(stream),
         Category::ReturnKeyword => parse_return_statement(stream),
         Category::OpenBrace => parse_block_statement(stream),
-        _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [Category::Identifier, Category::Semicolon].to_vec();
-            Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            })
+        category if EXPRESSION_START.contains(&category) => {
+            let expression = parse_expression(stream)?;
+            parse_token(Category::Semicolon, "after expression statement", stream)?;
+            Ok(Statement::new_expression(expression))
         }
+        _ => Err(unexpected_token(&STATEMENT_START, "", stream)),
     }
 }
 
-fn parse_if_else_statement(stream: &mut TokenStream) -> Result<Statement, ParseError> {
+fn parse_if_else_statement<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Statement, ParseError<'src>> {
     let (condition, body) = parse_if(stream)?;
     let else_body = parse_else(stream)?;
     Ok(Statement::new_if_else(condition, body, else_body))
 }
 
-fn parse_if(stream: &mut TokenStream) -> Result<(Expression, Statement), ParseError> {
-    parse_token(Category::IfKeyword, stream)?;
-    parse_token(Category::OpenParen, stream)?;
+fn parse_if<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<(Expression, Statement), ParseError<'src>> {
+    parse_token(Category::IfKeyword, "at start of if statement", stream)?;
+    parse_token(Category::OpenParen, "after 'if'", stream)?;
     let condition = parse_expression(stream)?;
-    parse_token(Category::CloseParen, stream)?;
+    parse_token(Category::CloseParen, "after if condition", stream)?;
     let body = parse_statement(stream)?;
     Ok((condition, body))
 }
 
-fn parse_else(stream: &mut TokenStream) -> Result<Option<Statement>, ParseError> {
-    if stream.peek(1).unwrap().category == Category::ElseKeyword {
-        parse_token(Category::ElseKeyword, stream)?;
+fn parse_else<'src>(stream: &mut TokenStream<'src>) -> Result<Option<Statement>, ParseError<'src>> {
+    if stream.peek(1).map(|token| token.category) == Some(Category::ElseKeyword) {
+        stream.consume(1);
         return Ok(Some(parse_statement(stream)?));
     }
     Ok(None)
 }
 
-fn parse_for_statement(stream: &mut TokenStream) -> Result<Statement, ParseError> {
-    parse_token(Category::ForKeyword, stream)?;
-    parse_token(Category::OpenParen, stream)?;
-    let one_ahead = stream.peek(1).unwrap().category;
-    let two_ahead = stream.peek(2).unwrap().category;
-    let three_ahead = stream.peek(3).unwrap().category;
-    match [one_ahead, two_ahead, three_ahead] {
-        [Category::Semicolon, Category::Semicolon, Category::CloseParen] => {
-            stream.consume(3);
-            return Ok(Statement::new_for(None, None, None, parse_statement(stream)?));
-        }
-        _ => {}
-    }
-    let init_expr = parse_expression(stream)?;
-    parse_token(Category::Semicolon, stream)?;
-    let condition_expr = parse_expression(stream)?;
-    parse_token(Category::Semicolon, stream)?;
-    let next_expression = parse_expression(stream)?;
-    parse_token(Category::CloseParen, stream)?;
+fn parse_for_statement<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Statement, ParseError<'src>> {
+    parse_token(Category::ForKeyword, "at start of for statement", stream)?;
+    parse_token(Category::OpenParen, "after 'for'", stream)?;
+    let init_expr =
+        parse_optional_expression(Category::Semicolon, "after for loop initializer", stream)?;
+    let condition_expr =
+        parse_optional_expression(Category::Semicolon, "after for loop condition", stream)?;
+    let next_expression =
+        parse_optional_expression(Category::CloseParen, "after for loop step", stream)?;
     let for_statement = parse_statement(stream)?;
-    Ok(Statement::new_for(Some(init_expr), Some(condition_expr), Some(next_expression), for_statement))
+    Ok(Statement::new_for(init_expr, condition_expr, next_expression, for_statement))
 }
 
-fn parse_print_statement(stream: &mut TokenStream) -> Result<Statement, ParseError> {
-    parse_token(Category::PrintKeyword, stream)?;
-    let mut print_statement = Statement::new_print(None);
-    if stream.peek(1).unwrap().category == Category::Semicolon {
-        parse_token(Category::Semicolon, stream)?;
-        return Ok(print_statement)
+/// Parses an expression that may be omitted followed by the terminator.
+fn parse_optional_expression<'src>(
+    terminator: Category,
+    context: &'static str,
+    stream: &mut TokenStream<'src>,
+) -> Result<Option<Expression>, ParseError<'src>> {
+    error_on_empty_stream(stream)?;
+    let expression = if peek_token(stream, 1)?.category == terminator {
+        None
     } else {
-        print_statement.attach_most_next(Statement::new_expression(parse_expression(stream)?));
+        Some(parse_expression(stream)?)
+    };
+    parse_token(terminator, context, stream)?;
+    Ok(expression)
+}
+
+fn parse_while_statement<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Statement, ParseError<'src>> {
+    parse_token(Category::WhileKeyword, "at start of while statement", stream)?;
+    parse_token(Category::OpenParen, "after 'while'", stream)?;
+    let condition = parse_expression(stream)?;
+    parse_token(Category::CloseParen, "after while condition", stream)?;
+    let body = parse_statement(stream)?;
+    Ok(Statement::new_while(condition, body))
+}
+
+fn parse_print_statement<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Statement, ParseError<'src>> {
+    parse_token(Category::PrintKeyword, "at start of print statement", stream)?;
+    let mut print_args = Vec::new();
+    if peek_token(stream, 1)?.category == Category::Semicolon {
+        stream.consume(1);
+        return Ok(Statement::new_print(print_args));
     }
+    print_args.push(parse_expression(stream)?);
     loop {
-        if stream.peek(1).unwrap().category == Category::Semicolon {
-            parse_token(Category::Semicolon, stream)?;
-            return Ok(print_statement)
-        } else {
-            parse_token(Category::Comma, stream)?;
-            print_statement.attach_most_next(Statement::new_expression(parse_expression(stream)?));
+        match peek_token(stream, 1)?.category {
+            Category::Semicolon => {
+                stream.consume(1);
+                return Ok(Statement::new_print(print_args));
+            }
+            Category::Comma => {
+                stream.consume(1);
+                print_args.push(parse_expression(stream)?);
+            }
+            _ => {
+                return Err(unexpected_token(
+                    &[Category::Comma, Category::Semicolon],
+                    "after print argument",
+                    stream,
+                ))
+            }
         }
     }
 }
 
-fn parse_return_statement(stream: &mut TokenStream) -> Result<Statement, ParseError> {
-    parse_token(Category::ReturnKeyword, stream)?;
+fn parse_return_statement<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Statement, ParseError<'src>> {
+    parse_token(Category::ReturnKeyword, "at start of return statement", stream)?;
     let return_statment = Statement::new_return(parse_expression(stream)?);
-    parse_token(Category::Semicolon, stream)?;
+    parse_token(Category::Semicolon, "after return statement", stream)?;
     Ok(return_statment)
 }
 
-fn parse_block_statement(stream: &mut TokenStream) -> Result<Statement, ParseError> {
-    parse_token(Category::OpenBrace, stream)?;
-    let mut block = Statement::new_block(None);
+fn parse_block_statement<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Statement, ParseError<'src>> {
+    parse_token(Category::OpenBrace, "at start of block", stream)?;
+    let mut statements = Vec::new();
     loop {
         error_on_empty_stream(stream)?;
-        if stream.peek(1).unwrap().category == Category::CloseBrace {
-            parse_token(Category::CloseBrace, stream)?;
-            return Ok(block);
-        }
-        block.attach_most_next(parse_statement(stream)?);
-    }
-}
-
-fn parse_expression(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    parse_assignment(stream)
-}
-
-fn parse_assignment(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    let logical_expr = parse_logical(stream)?; 
-    let assignment_expr = parse_assignment_a(stream)?;
-    if assignment_expr.is_some() {
-        let mut assignment_expr = assignment_expr.unwrap();
-        assignment_expr.attach_leftmost(logical_expr);
-        return Ok(assignment_expr);
-    }
-    Ok(logical_expr)
-}
-
-fn parse_assignment_a(stream: &mut TokenStream) -> Result<Option<Expression>, ParseError> {
-    let one_ahead = stream.peek(1).unwrap().category;
-    match one_ahead {
-        Category::Equal => {
-            stream.consume(1);
-            let logical_expr = parse_logical(stream)?;
-            let assignment_expr = parse_assignment_a(stream)?;
-            let result = make_recurrent_binary_expression(ExpressionKind::Assignment, logical_expr, assignment_expr);
-            Ok(Some(result))
-        }
-        _ => Ok(None)
-    }
-}
-
-fn parse_logical(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    let comparison_expr = parse_comparison(stream)?;
-    let logical_expr = parse_logical_a(stream)?;
-    if logical_expr.is_some() {
-        let mut logical_expr = logical_expr.unwrap();
-        logical_expr.attach_leftmost(comparison_expr);
-        return Ok(logical_expr);
-    }
-    Ok(comparison_expr)
-}
-
-fn parse_logical_a(stream: &mut TokenStream) -> Result<Option<Expression>, ParseError> {
-    error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Pipe => {
-            parse_token(Category::Pipe, stream)?;
-            parse_token(Category::Pipe, stream)?;
-            let comparison_expr = parse_comparison(stream)?;
-            let logical_expr = parse_logical_a(stream)?;
-            let result =
-                make_recurrent_binary_expression(ExpressionKind::Or, comparison_expr, logical_expr);
-            Ok(Some(result))
-        }
-        Category::Ampersand => {
-            parse_token(Category::Ampersand, stream)?;
-            parse_token(Category::Ampersand, stream)?;
-            let comparison_expr = parse_comparison(stream)?;
-            let logical_expr = parse_logical_a(stream)?;
-            let result =
-                make_recurrent_binary_expression(ExpressionKind::Or, comparison_expr, logical_expr);
-            Ok(Some(result))
-        }
-        _ => Ok(None),
-    }
-}
-
-fn parse_comparison(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    let arithmetical_add_sub_expr = parse_arithmetical_add_sub(stream)?;
-    let comparison_expr = parse_comparison_a(stream)?;
-    if comparison_expr.is_some() {
-        let mut comparison_expr = comparison_expr.unwrap();
-        comparison_expr.attach_leftmost(arithmetical_add_sub_expr);
-        return Ok(comparison_expr);
-    }
-    Ok(arithmetical_add_sub_expr)
-}
-
-fn parse_comparison_a(stream: &mut TokenStream) -> Result<Option<Expression>, ParseError> {
-    error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Less => {
-            parse_token(Category::Less, stream)?;
-            Ok(parse_comparison_a_open_angle(stream)?)
-        }
-        Category::More => {
-            parse_token(Category::More, stream)?;
-            Ok(parse_comparison_a_close_angle(stream)?)
-        }
-        Category::Equal => {
-            if stream.peek(2).unwrap().category == Category::Equal {
-                parse_token(Category::Equal, stream)?;
-                parse_token(Category::Equal, stream)?;
-                let expr = parse_arithmetical_add_sub(stream)?;
-                let recurrent_expr = parse_comparison_a(stream)?;
-                let result =
-                    make_recurrent_binary_expression(ExpressionKind::Equal, expr, recurrent_expr);
-                Ok(Some(result))
-            } else {
-                Ok(None)
+        match peek_token(stream, 1)?.category {
+            Category::CloseBrace => {
+                stream.consume(1);
+                return Ok(Statement::new_block(Statement::link(statements)));
+            }
+            category if STATEMENT_START.contains(&category) => (),
+            _ => {
+                let mut expected = vec![Category::CloseBrace];
+                expected.extend_from_slice(&STATEMENT_START);
+                let error = unexpected_token(&expected, "in block", stream);
+                if !stream.recovering {
+                    return Err(error);
+                }
+                stream.errors.push(error);
+                synchronize_statement(stream);
+                statements.push(Statement::new_error());
+                continue;
             }
         }
-        Category::Exclamation => {
-            parse_token(Category::Exclamation, stream)?;
-            parse_token(Category::Equal, stream)?;
-            let expr = parse_arithmetical_add_sub(stream)?;
-            let recurrent_expr = parse_comparison_a(stream)?;
-            let result =
-                make_recurrent_binary_expression(ExpressionKind::NotEqual, expr, recurrent_expr);
-            Ok(Some(result))
-        }
-        _ => Ok(None),
-    }
-}
-
-fn parse_comparison_a_open_angle(
-    stream: &mut TokenStream,
-) -> Result<Option<Expression>, ParseError> {
-    error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Equal => {
-            parse_token(Category::Equal, stream)?;
-            let expr = parse_arithmetical_add_sub(stream)?;
-            let recurrent_expr = parse_comparison_a(stream)?;
-            let result =
-                make_recurrent_binary_expression(ExpressionKind::LessEqual, expr, recurrent_expr);
-            Ok(Some(result))
-        }
-        _ => {
-            let expr = parse_arithmetical_add_sub(stream)?;
-            let recurrent_expr = parse_comparison_a(stream)?;
-            let result =
-                make_recurrent_binary_expression(ExpressionKind::Less, expr, recurrent_expr);
-            Ok(Some(result))
-        }
-    }
-}
-
-fn parse_comparison_a_close_angle(
-    stream: &mut TokenStream,
-) -> Result<Option<Expression>, ParseError> {
-    error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Equal => {
-            parse_token(Category::Equal, stream)?;
-            let expr = parse_arithmetical_add_sub(stream)?;
-            let recurrent_expr = parse_comparison_a(stream)?;
-            let result =
-                make_recurrent_binary_expression(ExpressionKind::MoreEqual, expr, recurrent_expr);
-            Ok(Some(result))
-        }
-        _ => {
-            let expr = parse_arithmetical_add_sub(stream)?;
-            let recurrent_expr = parse_comparison_a(stream)?;
-            let result =
-                make_recurrent_binary_expression(ExpressionKind::More, expr, recurrent_expr);
-            Ok(Some(result))
-        }
-    }
-}
-
-fn parse_arithmetical_add_sub(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    let arithmetical_mul_div_expr = parse_arithmetical_mul_div_mod(stream)?;
-    let arithmetical_add_sub_expr = parse_arithmetical_add_sub_a(stream)?;
-    if arithmetical_add_sub_expr.is_some() {
-        let mut arithmetical_add_sub_expr = arithmetical_add_sub_expr.unwrap();
-        arithmetical_add_sub_expr.attach_leftmost(arithmetical_mul_div_expr);
-        return Ok(arithmetical_add_sub_expr);
-    }
-    Ok(arithmetical_mul_div_expr)
-}
-
-fn parse_arithmetical_add_sub_a(
-    stream: &mut TokenStream,
-) -> Result<Option<Expression>, ParseError> {
-    error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Plus => {
-            parse_token(Category::Plus, stream)?;
-            let expr = parse_arithmetical_mul_div_mod(stream)?;
-            let recurrent_expr = parse_arithmetical_add_sub_a(stream)?;
-            Ok(Some(make_recurrent_binary_expression(
-                ExpressionKind::Addition,
-                expr,
-                recurrent_expr,
-            )))
-        }
-        Category::Minus => {
-            parse_token(Category::Minus, stream)?;
-            let expr = parse_arithmetical_mul_div_mod(stream)?;
-            let recurrent_expr = parse_arithmetical_add_sub_a(stream)?;
-            Ok(Some(make_recurrent_binary_expression(
-                ExpressionKind::Subtraction,
-                expr,
-                recurrent_expr,
-            )))
-        }
-        _ => Ok(None),
-    }
-}
-
-fn parse_arithmetical_mul_div_mod(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    let exponentiation_expr = parse_exponentiation(stream)?;
-    let arithmetical_mul_div_expr = parse_arithmetical_mul_div_mod_a(stream)?;
-    if arithmetical_mul_div_expr.is_some() {
-        let mut arithmetical_mul_div_expr = arithmetical_mul_div_expr.unwrap();
-        arithmetical_mul_div_expr.attach_leftmost(exponentiation_expr);
-        return Ok(arithmetical_mul_div_expr);
-    }
-    Ok(exponentiation_expr)
-}
-
-fn parse_arithmetical_mul_div_mod_a(
-    stream: &mut TokenStream,
-) -> Result<Option<Expression>, ParseError> {
-    error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Star => {
-            parse_token(Category::Star, stream)?;
-            let expr = parse_exponentiation(stream)?;
-            let recurrent_expr = parse_arithmetical_mul_div_mod_a(stream)?;
-            Ok(Some(make_recurrent_binary_expression(
-                ExpressionKind::Multiplication,
-                expr,
-                recurrent_expr,
-            )))
-        }
-        Category::Slash => {
-            parse_token(Category::Slash, stream)?;
-            let expr = parse_exponentiation(stream)?;
-            let recurrent_expr = parse_arithmetical_mul_div_mod_a(stream)?;
-            Ok(Some(make_recurrent_binary_expression(
-                ExpressionKind::Division,
-                expr,
-                recurrent_expr,
-            )))
-        }
-        Category::Percent => {
-            parse_token(Category::Percent, stream)?;
-            let expr = parse_exponentiation(stream)?;
-            let recurrent_expr = parse_arithmetical_mul_div_mod_a(stream)?;
-            Ok(Some(make_recurrent_binary_expression(
-                ExpressionKind::Modulo,
-                expr,
-                recurrent_expr,
-            )))
+        match parse_statement(stream) {
+            Ok(statement) => statements.push(statement),
+            Err(error) if stream.recovering && !stream.is_empty() => {
+                stream.errors.push(error);
+                synchronize_statement(stream);
+                statements.push(Statement::new_error());
+            }
+            Err(error) => return Err(error),
         }
-        _ => Ok(None),
     }
 }
 
-fn parse_exponentiation(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    let unary_expr = parse_unary(stream)?;
-    let exponentiation_expr = parse_exponentiation_a(stream)?;
-    if exponentiation_expr.is_some() {
-        let mut exponentiation_expr = exponentiation_expr.unwrap();
-        exponentiation_expr.attach_leftmost(unary_expr);
-        return Ok(exponentiation_expr);
-    }
-    Ok(unary_expr)
+fn parse_expression<'src>(stream: &mut TokenStream<'src>) -> Result<Expression, ParseError<'src>> {
+    parse_binary_expression(0, stream)
 }
 
-fn parse_exponentiation_a(stream: &mut TokenStream) -> Result<Option<Expression>, ParseError> {
-    error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Dash => {
-            parse_token(Category::Dash, stream)?;
-            let expr = parse_unary(stream)?;
-            let recurrent_expr = parse_exponentiation_a(stream)?;
-            Ok(Some(make_recurrent_binary_expression(
-                ExpressionKind::Power,
-                expr,
-                recurrent_expr,
-            )))
-        }
-        _ => Ok(None),
-    }
+/// Returns the kind of the binary expression built by the operator together
+/// with its left and right binding power. Operators binding more tightly from
+/// the right than from the left are right-associative.
+fn binary_operator(category: Category) -> Option<(ExpressionKind, u8, u8)> {
+    let operator = match category {
+        Category::Equal => (ExpressionKind::Assignment, 2, 1),
+        Category::DoublePipe => (ExpressionKind::Or, 3, 4),
+        Category::DoubleAmpersand => (ExpressionKind::And, 3, 4),
+        Category::Less => (ExpressionKind::Less, 5, 6),
+        Category::LessEqual => (ExpressionKind::LessEqual, 5, 6),
+        Category::More => (ExpressionKind::More, 5, 6),
+        Category::MoreEqual => (ExpressionKind::MoreEqual, 5, 6),
+        Category::DoubleEqual => (ExpressionKind::Equal, 5, 6),
+        Category::NotEqual => (ExpressionKind::NotEqual, 5, 6),
+        Category::Plus => (ExpressionKind::Addition, 7, 8),
+        Category::Minus => (ExpressionKind::Subtraction, 7, 8),
+        Category::Star => (ExpressionKind::Multiplication, 9, 10),
+        Category::Slash => (ExpressionKind::Division, 9, 10),
+        Category::Percent => (ExpressionKind::Modulo, 9, 10),
+        Category::Dash => (ExpressionKind::Power, 12, 11),
+        _ => return None,
+    };
+    Some(operator)
 }
 
-fn parse_unary(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    let unary_expr = parse_unary_a(stream)?;
-    let postfix_expr = parse_postfix(stream)?;
-    if unary_expr.is_some() {
-        let mut unary_expr = unary_expr.unwrap();
-        unary_expr.attach_leftmost(postfix_expr);
-        return Ok(unary_expr);
+/// Parses binary expressions whose operators bind at least as tightly as
+/// `min_power` using precedence climbing.
+fn parse_binary_expression<'src>(
+    min_power: u8,
+    stream: &mut TokenStream<'src>,
+) -> Result<Expression, ParseError<'src>> {
+    let mut expression = parse_unary(stream)?;
+    loop {
+        error_on_empty_stream(stream)?;
+        let (kind, left_power, right_power) = match binary_operator(peek_token(stream, 1)?.category) {
+            Some(operator) => operator,
+            None => return Ok(expression),
+        };
+        if left_power < min_power {
+            return Ok(expression);
+        }
+        stream.consume(1);
+        let right = parse_binary_expression(right_power, stream)?;
+        expression = Expression::new_binary(kind, expression, right);
     }
-    Ok(postfix_expr)
 }
 
-fn parse_unary_a(stream: &mut TokenStream) -> Result<Option<Expression>, ParseError> {
+fn parse_unary<'src>(stream: &mut TokenStream<'src>) -> Result<Expression, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
+    match peek_token(stream, 1)?.category {
+        // minus directly before an integer is a part of the literal
+        Category::Minus if is_negative_integer(stream) => parse_postfix(stream),
         Category::Minus => {
-            parse_token(Category::Minus, stream)?;
-            let minus = Expression {
-                kind: ExpressionKind::Minus,
-                left: parse_unary_a(stream)?.map(|x| Box::new(x)),
-                right: None,
-                value: None,
-            };
-            Ok(Some(minus))
+            stream.consume(1);
+            Ok(Expression::new_unary(ExpressionKind::Minus, parse_unary(stream)?))
         }
         Category::Exclamation => {
-            parse_token(Category::Exclamation, stream)?;
-            let exclemation = Expression {
-                kind: ExpressionKind::Negation,
-                left: parse_unary_a(stream)?.map(|x| Box::new(x)),
-                right: None,
-                value: None,
-            };
-            Ok(Some(exclemation))
+            stream.consume(1);
+            Ok(Expression::new_unary(ExpressionKind::Negation, parse_unary(stream)?))
         }
-        _ => Ok(None),
+        _ => parse_postfix(stream),
     }
 }
 
-fn parse_postfix(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    let subscript_expr = parse_subscript_call(stream)?;
-    let postfix_expr = parse_postfix_a(stream)?;
-    if postfix_expr.is_some() {
-        let mut postfix_expr = postfix_expr.unwrap();
-        postfix_expr.attach_leftmost(subscript_expr);
-        return Ok(postfix_expr);
-    }
-    Ok(subscript_expr)
-}
-
-fn parse_postfix_a(stream: &mut TokenStream) -> Result<Option<Expression>, ParseError> {
-    error_on_empty_stream(stream)?;
-    if stream.remain() < 2 {
-        return Ok(None);
-    }
-    let one_ahead = stream.peek(1).unwrap().category;
-    let two_ahead = stream.peek(2).unwrap().category;
-    match [one_ahead, two_ahead] {
-        [Category::Plus, Category::Plus] => {
-            parse_token(Category::Plus, stream)?;
-            parse_token(Category::Plus, stream)?;
-            Ok(Some(Expression {
-                kind: ExpressionKind::Incrementation,
-                left: parse_postfix_a(stream)?.map(|x| Box::new(x)),
-                right: None,
-                value: None,
-            }))
-        }
-        [Category::Minus, Category::Minus] => {
-            parse_token(Category::Minus, stream)?;
-            parse_token(Category::Minus, stream)?;
-            Ok(Some(Expression {
-                kind: ExpressionKind::Decrementation,
-                left: parse_postfix_a(stream)?.map(|x| Box::new(x)),
-                right: None,
-                value: None,
-            }))
-        }
-        _ => Ok(None),
+fn parse_postfix<'src>(stream: &mut TokenStream<'src>) -> Result<Expression, ParseError<'src>> {
+    let mut expression = parse_subscript_call(stream)?;
+    loop {
+        error_on_empty_stream(stream)?;
+        let kind = match peek_token(stream, 1)?.category {
+            Category::DoublePlus => ExpressionKind::Incrementation,
+            Category::DoubleMinus => ExpressionKind::Decrementation,
+            _ => return Ok(expression),
+        };
+        stream.consume(1);
+        expression = Expression::new_unary(kind, expression);
     }
 }
 
-fn parse_subscript_call(stream: &mut TokenStream) -> Result<Expression, ParseError> {
+fn parse_subscript_call<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Expression, ParseError<'src>> {
     let value_expr = parse_value(stream)?;
     let mut subscripts_and_calls = parse_subscript_call_a(stream)?;
     if !subscripts_and_calls.is_empty() {
@@ -749,22 +858,22 @@ fn parse_subscript_call(stream: &mut TokenStream) -> Result<Expression, ParseErr
     Ok(value_expr)
 }
 
-fn parse_call(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    parse_token(Category::OpenParen, stream)?;
-    let args_expr = parse_function_arguments(stream)?;
-    parse_token(Category::CloseParen, stream)?;
+fn parse_call<'src>(stream: &mut TokenStream<'src>) -> Result<Expression, ParseError<'src>> {
+    parse_token(Category::OpenParen, "at start of function arguments", stream)?;
+    let arguments = parse_function_arguments(stream)?;
+    parse_token(Category::CloseParen, "after function arguments", stream)?;
     Ok(Expression {
         kind: ExpressionKind::FunctionCall,
         left: None,
-        right: args_expr.map(|x| Box::new(x)),
-        value: None,
+        right: None,
+        value: Some(ExpressionValue::Arguments(arguments)),
     })
 }
 
-fn parse_subscript(stream: &mut TokenStream) -> Result<Expression, ParseError> {
-    parse_token(Category::OpenBracket, stream)?;
+fn parse_subscript<'src>(stream: &mut TokenStream<'src>) -> Result<Expression, ParseError<'src>> {
+    parse_token(Category::OpenBracket, "at start of array index", stream)?;
     let expr = parse_expression(stream)?;
-    parse_token(Category::CloseBracket, stream)?;
+    parse_token(Category::CloseBracket, "after array index", stream)?;
     Ok(Expression {
         kind: ExpressionKind::Subscript,
         left: None,
@@ -773,11 +882,13 @@ fn parse_subscript(stream: &mut TokenStream) -> Result<Expression, ParseError> {
     })
 }
 
-fn parse_subscript_call_a(stream: &mut TokenStream) -> Result<Vec<Expression>, ParseError> {
+fn parse_subscript_call_a<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Vec<Expression>, ParseError<'src>> {
     error_on_empty_stream(stream)?;
     let mut subscripts_and_calls = Vec::new();
     loop {
-        match stream.peek(1).unwrap().category {
+        match peek_token(stream, 1)?.category {
             // function call
             Category::OpenParen => {
                 subscripts_and_calls.push(parse_call(stream)?);
@@ -789,29 +900,36 @@ fn parse_subscript_call_a(stream: &mut TokenStream) -> Result<Vec<Expression>, P
             _ => break,
         }
     }
-    return Ok(subscripts_and_calls);
+    Ok(subscripts_and_calls)
 }
 
-fn parse_value(stream: &mut TokenStream) -> Result<Expression, ParseError> {
+fn parse_value<'src>(stream: &mut TokenStream<'src>) -> Result<Expression, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
+    match peek_token(stream, 1)?.category {
         Category::OpenParen => {
-            parse_token(Category::OpenParen, stream)?;
+            stream.consume(1);
             let expression: Expression = parse_expression(stream)?;
-            parse_token(Category::CloseParen, stream)?;
+            parse_token(Category::CloseParen, "after parenthesised expression", stream)?;
             Ok(expression)
         }
         Category::Identifier
         | Category::Integer
         | Category::Float
+        | Category::Boolean
         | Category::Character
         | Category::Text => {
-            let token = stream.next().unwrap();
-            let value_expression = value_to_expression_value(token.lexeme, token.category);
+            let token = next_token(stream)?;
+            let value_expression = value_to_expression_value(token, false)?;
+            Ok(Expression::new_value(value_expression))
+        }
+        Category::Minus if is_negative_integer(stream) => {
+            stream.consume(1);
+            let token = next_token(stream)?;
+            let value_expression = value_to_expression_value(token, true)?;
             Ok(Expression::new_value(value_expression))
         }
         Category::OpenBrace => {
-            parse_token(Category::OpenBrace, stream)?;
+            stream.consume(1);
             let mut array_values: Vec<Expression> = Vec::new();
             let mut array_expr = Expression {
                 kind: ExpressionKind::Array,
@@ -819,343 +937,661 @@ fn parse_value(stream: &mut TokenStream) -> Result<Expression, ParseError> {
                 right: None,
                 value: None,
             };
-            if stream.peek(1).unwrap().category == Category::CloseBrace {
-                parse_token(Category::CloseBrace, stream)?;
+            if peek_token(stream, 1)?.category == Category::CloseBrace {
+                stream.consume(1);
                 array_expr.value = Some(ExpressionValue::Array(array_values));
                 return Ok(array_expr);
             }
             array_values.push(parse_expression(stream)?);
             loop {
-                if stream.peek(1).unwrap().category == Category::CloseBrace {
-                    parse_token(Category::CloseBrace, stream)?;
-                    array_expr.value = Some(ExpressionValue::Array(array_values));
-                    return Ok(array_expr);
+                match peek_token(stream, 1)?.category {
+                    Category::CloseBrace => {
+                        stream.consume(1);
+                        array_expr.value = Some(ExpressionValue::Array(array_values));
+                        return Ok(array_expr);
+                    }
+                    Category::Comma => {
+                        stream.consume(1);
+                        array_values.push(parse_expression(stream)?);
+                    }
+                    _ => {
+                        return Err(unexpected_token(
+                            &[Category::Comma, Category::CloseBrace],
+                            "after array element",
+                            stream,
+                        ))
+                    }
                 }
-                parse_token(Category::Comma, stream)?;
-                array_values.push(parse_expression(stream)?);
             }
         }
-        _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [Category::Identifier, Category::Integer].to_vec();
-            Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            })
-        }
+        _ => Err(unexpected_token(&EXPRESSION_START, "", stream)),
     }
 }
 
-fn parse_function_arguments(stream: &mut TokenStream) -> Result<Option<Expression>, ParseError> {
+fn parse_function_arguments<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Vec<Expression>, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    let mut first_arg_expr = Expression {
-        kind: ExpressionKind::FunctionArg,
-        left: None,
-        right: None,
-        value: None,
-    };
-    match stream.peek(1).unwrap().category {
-        Category::CloseParen => return Ok(None),
-        Category::Identifier
-        | Category::Integer
-        | Category::Float
-        | Category::Character
-        | Category::Text => {
-            first_arg_expr.attach_rightmost(parse_expression(stream)?);
+    let mut arguments = Vec::new();
+    match peek_token(stream, 1)?.category {
+        Category::CloseParen => return Ok(arguments),
+        category if EXPRESSION_START.contains(&category) => {
+            arguments.push(parse_expression(stream)?);
         }
         _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [Category::Identifier].to_vec();
-            return Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            });
+            let mut expected = vec![Category::CloseParen];
+            expected.extend_from_slice(&EXPRESSION_START);
+            return Err(unexpected_token(&expected, "in function arguments", stream));
         }
     }
     loop {
-        match stream.peek(1).unwrap().category {
+        match peek_token(stream, 1)?.category {
             Category::Comma => {
-                parse_token(Category::Comma, stream)?;
-                first_arg_expr.attach_rightmost(parse_expression(stream)?);
+                stream.consume(1);
+                arguments.push(parse_expression(stream)?);
             }
-            Category::CloseParen => return Ok(Some(first_arg_expr)),
+            Category::CloseParen => return Ok(arguments),
             _ => {
-                let unexpected = stream.peek(1).unwrap();
-                let expected = [Category::Identifier].to_vec();
-                return Err(ParseError::UnexpectedToken {
-                    unexpected,
-                    expected,
-                });
+                return Err(unexpected_token(
+                    &[Category::Comma, Category::CloseParen],
+                    "after function argument",
+                    stream,
+                ))
             }
         }
     }
 }
 
-fn parse_id_parameter(stream: &mut TokenStream) -> Result<(), ParseError> {
+fn parse_id_parameter<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<ParameterList, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    parse_token(Category::Identifier, stream)?;
-    parse_token(Category::Colon, stream)?;
-    parse_empty_type(stream)?;
-    Ok(())
+    let start = peek_token(stream, 1)?.span;
+    let name = parse_token(Category::Identifier, "at start of parameter", stream)?.0;
+    parse_token(Category::Colon, "after parameter name", stream)?;
+    let type_ = parse_empty_type(stream)?;
+    let span = start.to(stream.peek_previous().unwrap().span);
+    Ok(ParameterList::new(Some(name), type_, span))
 }
 
-fn parse_non_id_parameter(stream: &mut TokenStream) -> Result<(), ParseError> {
+fn parse_non_id_parameter<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<ParameterList, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    parse_empty_type(stream)?;
-    Ok(())
+    let start = peek_token(stream, 1)?.span;
+    let type_ = parse_empty_type(stream)?;
+    let span = start.to(stream.peek_previous().unwrap().span);
+    Ok(ParameterList::new(None, type_, span))
 }
 
-fn parse_id_parameters(stream: &mut TokenStream) -> Result<(), ParseError> {
+fn parse_id_parameters<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Option<ParameterList>, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    parse_token(Category::OpenParen, stream)?;
-    match stream.peek(1).unwrap().category {
+    parse_token(Category::OpenParen, "after function return type", stream)?;
+    error_on_empty_stream(stream)?;
+    let mut parameters = match peek_token(stream, 1)?.category {
         Category::CloseParen => {
-            parse_token(Category::CloseParen, stream)?;
-            return Ok(());
-        }
-        Category::Identifier => {
-            parse_id_parameter(stream)?;
+            stream.consume(1);
+            return Ok(None);
         }
+        Category::Identifier => parse_id_parameter(stream)?,
         _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [Category::Identifier].to_vec();
-            return Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            });
+            return Err(unexpected_token(
+                &[Category::CloseParen, Category::Identifier],
+                "in parameter list",
+                stream,
+            ))
         }
-    }
+    };
     loop {
-        match stream.peek(1).unwrap().category {
+        error_on_empty_stream(stream)?;
+        match peek_token(stream, 1)?.category {
             Category::CloseParen => {
-                parse_token(Category::CloseParen, stream)?;
-                return Ok(());
+                stream.consume(1);
+                return Ok(Some(parameters));
             }
             Category::Comma => {
-                parse_token(Category::Comma, stream)?;
-                parse_id_parameter(stream)?;
+                stream.consume(1);
+                parameters.attach_most_next(parse_id_parameter(stream)?);
             }
             _ => {
-                let unexpected = stream.peek(1).unwrap();
-                let expected = [Category::Identifier].to_vec();
-                return Err(ParseError::UnexpectedToken {
-                    unexpected,
-                    expected,
-                });
+                return Err(unexpected_token(
+                    &[Category::Comma, Category::CloseParen],
+                    "after parameter",
+                    stream,
+                ))
             }
         }
     }
 }
 
-fn parse_non_id_parameters(stream: &mut TokenStream) -> Result<(), ParseError> {
+fn parse_non_id_parameters<'src>(
+    stream: &mut TokenStream<'src>,
+) -> Result<Option<ParameterList>, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    parse_token(Category::OpenParen, stream)?;
-    match stream.peek(1).unwrap().category {
+    parse_token(Category::OpenParen, "after function return type", stream)?;
+    error_on_empty_stream(stream)?;
+    let mut parameters = match peek_token(stream, 1)?.category {
         Category::CloseParen => {
-            parse_token(Category::CloseParen, stream)?;
-            return Ok(());
+            stream.consume(1);
+            return Ok(None);
         }
-        Category::Identifier | Category::ArrayKeyword | Category::FunctionKeyword => {
-            parse_empty_type(stream)?;
+        category
+            if is_type_name(category)
+                || category == Category::ArrayKeyword
+                || category == Category::FunctionKeyword =>
+        {
+            parse_non_id_parameter(stream)?
         }
         _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [Category::Identifier].to_vec();
-            return Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            });
+            let mut expected = vec![Category::CloseParen];
+            expected.extend_from_slice(&TYPE_START);
+            return Err(unexpected_token(&expected, "in parameter list", stream));
         }
-    }
+    };
     loop {
-        match stream.peek(1).unwrap().category {
+        error_on_empty_stream(stream)?;
+        match peek_token(stream, 1)?.category {
             Category::CloseParen => {
-                parse_token(Category::CloseParen, stream)?;
-                return Ok(());
+                stream.consume(1);
+                return Ok(Some(parameters));
             }
             Category::Comma => {
-                parse_token(Category::Comma, stream)?;
-                parse_empty_type(stream)?;
+                stream.consume(1);
+                parameters.attach_most_next(parse_non_id_parameter(stream)?);
             }
             _ => {
-                let unexpected = stream.peek(1).unwrap();
-                let expected = [Category::Identifier].to_vec();
-                return Err(ParseError::UnexpectedToken {
-                    unexpected,
-                    expected,
-                });
+                return Err(unexpected_token(
+                    &[Category::Comma, Category::CloseParen],
+                    "after parameter",
+                    stream,
+                ))
             }
         }
     }
 }
 
-fn parse_full_type(stream: &mut TokenStream) -> Result<Type, ParseError> {
+fn parse_full_type<'src>(stream: &mut TokenStream<'src>) -> Result<Type, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Identifier => {
-            let type_name = parse_token(Category::Identifier, stream)?.0;
-            let type_option = Type::from_name(type_name);
-            if type_option.is_none() {
-                return Err(ParseError::UnknownTypeIdentifier{token: stream.peek_previous().unwrap()});
-            }
-            return Ok(type_option.unwrap());
-        }
+    match peek_token(stream, 1)?.category {
+        category if is_type_name(category) => parse_type_name(stream),
         Category::ArrayKeyword => {
             let mut type_ = Type {kind: TypeKind::Array,
                               subtype: None,
+                              size: None,
                               param_list: None,
                             };
-            parse_token(Category::ArrayKeyword, stream)?;
-            parse_token(Category::OpenBracket, stream)?;
-            parse_token(Category::Integer, stream)?;
-            parse_token(Category::CloseBracket, stream)?;
+            stream.consume(1);
+            parse_token(Category::OpenBracket, "after 'array'", stream)?;
+            type_.size = Some(parse_array_size(stream)?);
+            parse_token(Category::CloseBracket, "after array size", stream)?;
             type_.subtype = Some(Box::new(parse_full_type(stream)?));
-            return Ok(type_);
+            Ok(type_)
         }
         Category::FunctionKeyword => {
             let mut type_ = Type {kind: TypeKind::Function,
                                   subtype: None,
+                                  size: None,
                                   param_list: None,
                                  };
-            parse_token(Category::FunctionKeyword, stream)?;
+            stream.consume(1);
             type_.subtype = Some(Box::new(parse_empty_type(stream)?));
-            parse_id_parameters(stream)?;
-            return Ok(type_);
-        }
-        _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [
-                Category::Identifier,
-                Category::ArrayKeyword,
-                Category::FunctionKeyword,
-            ]
-            .to_vec();
-            return Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            });
+            type_.param_list = parse_id_parameters(stream)?.map(Box::new);
+            Ok(type_)
         }
+        _ => Err(unexpected_token(&TYPE_START, "", stream)),
     }
 }
 
-fn parse_empty_type(stream: &mut TokenStream) -> Result<Type, ParseError> {
+fn parse_empty_type<'src>(stream: &mut TokenStream<'src>) -> Result<Type, ParseError<'src>> {
     error_on_empty_stream(stream)?;
-    match stream.peek(1).unwrap().category {
-        Category::Identifier => {
-            let type_name = parse_token(Category::Identifier, stream)?.0;
-            let type_ = Type::from_name(type_name).unwrap();
-            return Ok(type_);
-        }
+    match peek_token(stream, 1)?.category {
+        category if is_type_name(category) => parse_type_name(stream),
         Category::ArrayKeyword => {
             let mut type_ = Type {kind: TypeKind::Array,
                                   subtype: None,
+                                  size: None,
                                   param_list: None,
                                  };
-            parse_token(Category::ArrayKeyword, stream)?;
-            parse_token(Category::OpenBracket, stream)?;
-            parse_token(Category::CloseBracket, stream)?;
+            stream.consume(1);
+            parse_token(Category::OpenBracket, "after 'array'", stream)?;
+            parse_token(Category::CloseBracket, "in parameter array type", stream)?;
             type_.subtype = Some(Box::new(parse_empty_type(stream)?));
-            return Ok(type_);
+            Ok(type_)
         }
         Category::FunctionKeyword => {
             let mut type_ = Type {kind: TypeKind::Function,
                                   subtype: None,
+                                  size: None,
                                   param_list: None,
                                  };
-            parse_token(Category::FunctionKeyword, stream)?;
+            stream.consume(1);
             type_.subtype = Some(Box::new(parse_empty_type(stream)?));
-            parse_non_id_parameters(stream)?;
-            return Ok(type_);
-        }
-        _ => {
-            let unexpected = stream.peek(1).unwrap();
-            let expected = [
-                Category::Identifier,
-                Category::ArrayKeyword,
-                Category::FunctionKeyword,
-            ]
-            .to_vec();
-            return Err(ParseError::UnexpectedToken {
-                unexpected,
-                expected,
-            });
+            type_.param_list = parse_non_id_parameters(stream)?.map(Box::new);
+            Ok(type_)
         }
+        _ => Err(unexpected_token(&TYPE_START, "", stream)),
     }
 }
 
-fn make_recurrent_binary_expression(
-    kind: ExpressionKind,
-    expr: Expression,
-    recurrent_expr: Option<Expression>,
-) -> Expression {
-    let normal_expr = Expression {
-        kind,
-        left: None,
-        right: Some(Box::new(expr)),
-        value: None,
-    };
-    if recurrent_expr.is_some() {
-        let mut recurrent_expr = recurrent_expr.unwrap();
-        recurrent_expr.left = Some(Box::new(normal_expr));
-        return recurrent_expr;
+fn parse_array_size<'src>(stream: &mut TokenStream<'src>) -> Result<usize, ParseError<'src>> {
+    error_on_empty_stream(stream)?;
+    let token = peek_token(stream, 1)?;
+    if token.category == Category::Minus && is_negative_integer(stream) {
+        return Err(ParseError::InvalidArraySize { token });
+    }
+    parse_token(Category::Integer, "as array size", stream)?;
+    let token = stream.peek_previous().unwrap();
+    match token.lexeme.parse::<usize>() {
+        Ok(0) => Err(ParseError::InvalidArraySize { token }),
+        Ok(size) => Ok(size),
+        Err(_) => Err(ParseError::IntegerOutOfRange { token }),
     }
-    return normal_expr;
 }
 
-fn value_to_expression_value(lexeme: String, category: Category) -> ExpressionValue {
-    match category {
-        Category::Identifier => ExpressionValue::Name(lexeme),
-        Category::Integer => ExpressionValue::Integer(lexeme.as_str().parse::<usize>().unwrap()),
-        Category::Float => ExpressionValue::Float(lexeme.as_str().parse::<f64>().unwrap()),
-        Category::Character => ExpressionValue::Character(lexeme.chars().next().unwrap()),
-        Category::Text => ExpressionValue::Text(lexeme),
-        _ => panic!("Read fake literal."),
+/// Identifiers are accepted as type names so that unknown types can be
+/// reported as such.
+fn is_type_name(category: Category) -> bool {
+    matches!(
+        category,
+        Category::Identifier
+            | Category::VoidKeyword
+            | Category::BooleanKeyword
+            | Category::CharKeyword
+            | Category::IntegerKeyword
+            | Category::FloatKeyword
+            | Category::StringKeyword
+    )
+}
+
+fn parse_type_name<'src>(stream: &mut TokenStream<'src>) -> Result<Type, ParseError<'src>> {
+    let token = next_token(stream)?;
+    match Type::from_name(String::from(token.lexeme)) {
+        Some(type_) if token.category != Category::Identifier => Ok(type_),
+        // the float type name is a keyword only with the float extension
+        Some(_) if token.lexeme == "float" => Err(ParseError::FloatsNotEnabled { token }),
+        _ => Err(ParseError::UnknownTypeIdentifier { token }),
+    }
+}
+
+fn is_negative_integer<'src>(stream: &mut TokenStream<'src>) -> bool {
+    stream.peek(2).map(|token| token.category) == Some(Category::Integer)
+}
+
+fn value_to_expression_value<'src>(
+    token: Token<'src>, negative: bool,
+) -> Result<ExpressionValue, ParseError<'src>> {
+    let value = match token.category {
+        Category::Identifier => ExpressionValue::Name(String::from(token.lexeme)),
+        Category::Integer => {
+            let literal = if negative {
+                format!("-{}", token.lexeme)
+            } else {
+                String::from(token.lexeme)
+            };
+            match literal.parse::<i64>() {
+                Ok(integer) => ExpressionValue::Integer(integer),
+                Err(_) => return Err(ParseError::IntegerOutOfRange { token }),
+            }
+        }
+        Category::Float => match token.lexeme.parse::<f64>() {
+            Ok(float) => ExpressionValue::Float(float),
+            Err(_) => return Err(malformed_literal(token)),
+        },
+        Category::Boolean => ExpressionValue::Boolean(token.lexeme == "true"),
+        Category::Character => match scanner::unescape(token.lexeme).chars().next() {
+            Some(character) => ExpressionValue::Character(character),
+            None => return Err(malformed_literal(token)),
+        },
+        Category::Text => ExpressionValue::Text(scanner::unescape(token.lexeme)),
+        _ => return Err(malformed_literal(token)),
+    };
+    Ok(value)
+}
+
+/// Reports a literal token whose lexeme could not have been produced by the
+/// scanner.
+fn malformed_literal(token: Token) -> ParseError {
+    let expected = [token.category].to_vec();
+    ParseError::UnexpectedToken {
+        unexpected: token,
+        expected,
+        context: "as a well-formed literal",
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ast::StatementKind;
+    use crate::source::Span;
 
     #[test]
     fn parse_correct_program() {
-        let mut declaration_tokens: Vec<Token> = Vec::new();
-        declaration_tokens.push(Token {
-            category: Category::Identifier,
-            lexeme: String::from("x"),
-            line: 1,
-            column: 1,
-        });
-        declaration_tokens.push(Token {
-            category: Category::Colon,
-            lexeme: String::from(":"),
-            line: 1,
-            column: 1,
-        });
-        declaration_tokens.push(Token {
-            category: Category::Identifier,
-            lexeme: String::from("string"),
-            line: 1,
-            column: 1,
-        });
-        declaration_tokens.push(Token {
-            category: Category::Equal,
-            lexeme: String::from("="),
-            line: 1,
-            column: 1,
-        });
-        declaration_tokens.push(Token {
-            category: Category::Identifier,
-            lexeme: String::from("something"),
-            line: 1,
-            column: 1,
-        });
-        declaration_tokens.push(Token {
-            category: Category::Semicolon,
-            lexeme: String::from(";"),
-            line: 1,
-            column: 1,
-        });
+        let declaration_tokens = vec![
+            Token {
+                category: Category::Identifier,
+                lexeme: "x",
+                line: 1,
+                column: 1,
+                span: Span::default(),
+            },
+            Token {
+                category: Category::Colon,
+                lexeme: ":",
+                line: 1,
+                column: 1,
+                span: Span::default(),
+            },
+            Token {
+                category: Category::StringKeyword,
+                lexeme: "string",
+                line: 1,
+                column: 1,
+                span: Span::default(),
+            },
+            Token {
+                category: Category::Equal,
+                lexeme: "=",
+                line: 1,
+                column: 1,
+                span: Span::default(),
+            },
+            Token {
+                category: Category::Identifier,
+                lexeme: "something",
+                line: 1,
+                column: 1,
+                span: Span::default(),
+            },
+            Token {
+                category: Category::Semicolon,
+                lexeme: ";",
+                line: 1,
+                column: 1,
+                span: Span::default(),
+            },
+        ];
         assert!(parse(&declaration_tokens).is_ok());
     }
+
+    #[test]
+    fn parse_decoded_literals() {
+        let tokens = scanner::tokenize(r#"c: char = '\n'; s: string = "say \"hi\"\\";"#).unwrap();
+        let declaration = parse(&tokens).unwrap();
+        let value = declaration.value.unwrap().value;
+        assert_eq!(value, Some(ExpressionValue::Character('\n')));
+        let value = declaration.next.unwrap().value.unwrap().value;
+        assert_eq!(value, Some(ExpressionValue::Text(String::from("say \"hi\"\\"))));
+    }
+
+    #[test]
+    fn parse_integer_limits() {
+        let tokens = scanner::tokenize("x: integer = -9223372036854775808;").unwrap();
+        let value = parse(&tokens).unwrap().value.unwrap().value;
+        assert_eq!(value, Some(ExpressionValue::Integer(i64::MIN)));
+        let tokens = scanner::tokenize("x: integer = 9223372036854775807;").unwrap();
+        let value = parse(&tokens).unwrap().value.unwrap().value;
+        assert_eq!(value, Some(ExpressionValue::Integer(i64::MAX)));
+        let tokens = scanner::tokenize("x: integer = 9223372036854775808;").unwrap();
+        match parse(&tokens) {
+            Err(ParseError::IntegerOutOfRange { token }) => assert_eq!(token.column, 14),
+            result => panic!("unexpected result {:?}", result),
+        }
+        let tokens = scanner::tokenize("x: integer = 99999999999999999999;").unwrap();
+        assert!(parse(&tokens).is_err());
+    }
+
+    #[test]
+    fn parse_float_extension() {
+        let extensions = scanner::Extensions { floats: true, ..scanner::Extensions::default() };
+        let tokens = scanner::tokenize_with_extensions("x: float = 1.5e-3;", extensions).unwrap();
+        let declaration = parse(&tokens).unwrap();
+        assert_eq!(declaration.type_.kind, TypeKind::Float);
+        assert_eq!(declaration.value.unwrap().value, Some(ExpressionValue::Float(1.5e-3)));
+        let tokens = scanner::tokenize("x: float;").unwrap();
+        match parse(&tokens) {
+            Err(error @ ParseError::FloatsNotEnabled { .. }) => assert_eq!(
+                error.to_string(),
+                "Type float at line 1 column 4 requires the float extension"
+            ),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parse_tokens_reports_scan_errors() {
+        let lexer = scanner::Lexer::new("x: integer = 1;\ny: integer = 2 @ 3;");
+        match parse_tokens(lexer) {
+            Err(ParseError::Scan(scanner::ScanError::CannotScanToken { line, column, .. })) => {
+                assert_eq!((line, column), (2, 16))
+            }
+            result => panic!("unexpected result {:?}", result),
+        }
+        let declaration = parse_tokens(scanner::Lexer::new("")).unwrap();
+        assert_eq!(declaration.name, "Empty file");
+    }
+
+    #[test]
+    fn parse_doc_comments() {
+        let stream = "/// Counter.\nx: integer;\n\n// plain\ny: integer;\n/** Adds. */\nf: function integer () = {\n    /// Local.\n    z: integer;\n}";
+        let (tokens, errors) = scanner::tokenize_with_trivia(stream, scanner::Extensions::default());
+        assert!(errors.is_empty());
+        let declaration = parse_with_trivia(&tokens).unwrap();
+        assert_eq!(declaration.doc.as_deref(), Some("Counter."));
+        let next = declaration.next.unwrap();
+        assert_eq!(next.doc, None);
+        let function = next.next.unwrap();
+        assert_eq!(function.doc.as_deref(), Some("Adds."));
+        let local = function.code.unwrap().next_statement.unwrap().declaration.unwrap();
+        assert_eq!(local.doc.as_deref(), Some("Local."));
+    }
+
+    #[test]
+    fn display_parse_errors() {
+        let tokens = scanner::tokenize("x: integer = (1;").unwrap();
+        let message = parse(&tokens).unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "Unexpected ';' at line 1 column 16, expected ')' after parenthesised expression"
+        );
+        let tokens = scanner::tokenize("x: integer = 1 + ;").unwrap();
+        let message = parse(&tokens).unwrap_err().to_string();
+        assert_eq!(message, "Unexpected ';' at line 1 column 18, expected an expression");
+        let tokens = scanner::tokenize("f: function void (a: array [] char, 1) = {}").unwrap();
+        let message = parse(&tokens).unwrap_err().to_string();
+        assert_eq!(
+            message,
+            "Unexpected integer literal '1' at line 1 column 37, expected identifier at start of parameter"
+        );
+        let error = parse_tokens(scanner::Lexer::new("x: integer = #;")).unwrap_err();
+        assert_eq!(error.to_string(), "Cannot scan token at line 1 column 14");
+    }
+
+    #[test]
+    fn parse_statements_starting_with_any_expression() {
+        let source = "f: function void () = { -x; !done; (x); 5; \"text\"; ++ ; }";
+        let tokens = scanner::tokenize(source).unwrap();
+        let (_, errors) = parse_recovering(&tokens);
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(
+            messages,
+            ["Unexpected '++' at line 1 column 52, expected '}' or a statement in block"]
+        );
+    }
+
+    #[test]
+    fn parse_while_loop() {
+        let tokens = scanner::tokenize("f: function void () = { while (x < 3) x++; }").unwrap();
+        let code = parse(&tokens).unwrap().code.unwrap();
+        let statement = code.next_statement.unwrap();
+        assert_eq!(statement.kind, StatementKind::While);
+        assert_eq!(statement.expression.unwrap().kind, ExpressionKind::Less);
+        assert_eq!(statement.body.unwrap().expression.unwrap().kind, ExpressionKind::Incrementation);
+    }
+
+    #[test]
+    fn parse_for_loops_with_omitted_expressions() {
+        let headers = [
+            ("i = 0; i < 3; i++", true, true, true),
+            ("i = 0; i < 3;", true, true, false),
+            ("i = 0;; i++", true, false, true),
+            ("i = 0;;", true, false, false),
+            ("; i < 3; i++", false, true, true),
+            ("; i < 3;", false, true, false),
+            (";; i++", false, false, true),
+            (";;", false, false, false),
+        ];
+        for (header, initial, condition, next) in headers.iter() {
+            let source = format!("f: function void () = {{ for ({}) print i; }}", header);
+            let tokens = scanner::tokenize(&source).unwrap();
+            let code = parse(&tokens).unwrap().code.unwrap();
+            let statement = code.next_statement.unwrap();
+            assert_eq!(statement.kind, StatementKind::For);
+            assert_eq!(statement.for_initial_expr.is_some(), *initial, "{}", header);
+            assert_eq!(statement.expression.is_some(), *condition, "{}", header);
+            assert_eq!(statement.for_next_expr.is_some(), *next, "{}", header);
+            assert_eq!(statement.body.unwrap().kind, StatementKind::Print);
+        }
+        let tokens = scanner::tokenize("f: function void () = { for (;) print i; }").unwrap();
+        assert!(parse(&tokens).is_err());
+    }
+
+    #[test]
+    fn parse_function_parameters() {
+        let source = "draw_box: function void (x: integer, pixels: array [] char, \
+                      cmp: function boolean (integer, integer));";
+        let tokens = scanner::tokenize(source).unwrap();
+        let type_ = parse(&tokens).unwrap().type_;
+        let x = type_.param_list.unwrap();
+        assert_eq!(x.name.as_deref(), Some("x"));
+        assert_eq!(x.type_.kind, TypeKind::Integer);
+        assert_eq!(&source[x.span.start..x.span.end], "x: integer");
+        let pixels = x.next.unwrap();
+        assert_eq!(pixels.name.as_deref(), Some("pixels"));
+        assert_eq!(pixels.type_.kind, TypeKind::Array);
+        assert_eq!(pixels.type_.subtype.unwrap().kind, TypeKind::Character);
+        assert_eq!(&source[pixels.span.start..pixels.span.end], "pixels: array [] char");
+        let cmp = pixels.next.unwrap();
+        assert_eq!(cmp.name.as_deref(), Some("cmp"));
+        assert_eq!(cmp.type_.kind, TypeKind::Function);
+        assert_eq!(cmp.type_.subtype.as_ref().unwrap().kind, TypeKind::Boolean);
+        let first = cmp.type_.param_list.unwrap();
+        assert_eq!(first.name, None);
+        assert_eq!(first.type_.kind, TypeKind::Integer);
+        assert_eq!(first.next.unwrap().type_.kind, TypeKind::Integer);
+        assert_eq!(cmp.next, None);
+        let tokens = scanner::tokenize("main: function void ();").unwrap();
+        assert_eq!(parse(&tokens).unwrap().type_.param_list, None);
+    }
+
+    #[test]
+    fn parse_print_arguments() {
+        let tokens = scanner::tokenize("f: function void () = { print x, y + 1; x = 2; }").unwrap();
+        let code = parse(&tokens).unwrap().code.unwrap();
+        let print = code.next_statement.unwrap();
+        assert_eq!(print.kind, StatementKind::Print);
+        let arguments: Vec<String> = print.print_args.iter().map(Expression::to_string).collect();
+        assert_eq!(arguments, ["x", "(y + 1)"]);
+        let assignment = print.next_statement.unwrap();
+        assert_eq!(assignment.kind, StatementKind::Expression);
+        assert_eq!(assignment.expression.unwrap().kind, ExpressionKind::Assignment);
+        assert!(assignment.next_statement.is_none());
+    }
+
+    #[test]
+    fn parse_nested_function_calls() {
+        let tokens = scanner::tokenize("x: integer = f(g(h(1), 2), -3);").unwrap();
+        let call = parse(&tokens).unwrap().value.unwrap();
+        assert_eq!(call.to_string(), "f(g(h(1), 2), -3)");
+        assert_eq!(call.kind, ExpressionKind::FunctionCall);
+        assert_eq!(call.left.unwrap().value, Some(ExpressionValue::Name(String::from("f"))));
+        let arguments = match call.value {
+            Some(ExpressionValue::Arguments(arguments)) => arguments,
+            value => panic!("unexpected value {:?}", value),
+        };
+        assert_eq!(arguments.len(), 2);
+        assert_eq!(arguments[0].to_string(), "g(h(1), 2)");
+        assert_eq!(arguments[1].value, Some(ExpressionValue::Integer(-3)));
+        let tokens = scanner::tokenize("x: integer = f();").unwrap();
+        let call = parse(&tokens).unwrap().value.unwrap();
+        assert_eq!(call.value, Some(ExpressionValue::Arguments(Vec::new())));
+    }
+
+    #[test]
+    fn parse_array_sizes() {
+        let tokens = scanner::tokenize("a: array [5] array [500] integer;").unwrap();
+        let type_ = parse(&tokens).unwrap().type_;
+        assert_eq!(type_.size, Some(5));
+        assert_eq!(type_.subtype.unwrap().size, Some(500));
+        let tokens = scanner::tokenize("f: function void (a: array [] integer);").unwrap();
+        let parameter = parse(&tokens).unwrap().type_.param_list.unwrap();
+        assert_eq!(parameter.type_.kind, TypeKind::Array);
+        assert_eq!(parameter.type_.size, None);
+        for (source, column) in [("a: array [0] integer;", 11), ("a: array [-3] integer;", 11)].iter() {
+            let tokens = scanner::tokenize(source).unwrap();
+            match parse(&tokens) {
+                Err(ParseError::InvalidArraySize { token }) => assert_eq!(token.column, *column),
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+        let tokens = scanner::tokenize("a: array [n] integer;").unwrap();
+        assert!(parse(&tokens).is_err());
+        let tokens = scanner::tokenize("a: array [99999999999999999999] integer;").unwrap();
+        match parse(&tokens) {
+            Err(ParseError::IntegerOutOfRange { .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parse_truncated_input() {
+        let tokens = scanner::tokenize("f: function void () = { if (x)").unwrap();
+        match parse(&tokens) {
+            Err(ParseError::UnexpectedEndOfTokens { line: 1, column: 30, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        let tokens = scanner::tokenize("x: integer = 1; if (x)").unwrap();
+        assert!(parse(&tokens).is_err());
+        let tokens = scanner::tokenize("f: function void (x: foo);").unwrap();
+        match parse(&tokens) {
+            Err(ParseError::UnknownTypeIdentifier { token }) => assert_eq!(token.lexeme, "foo"),
+            result => panic!("unexpected result {:?}", result),
+        }
+        let tokens = scanner::tokenize("x:").unwrap();
+        match parse(&tokens) {
+            Err(ParseError::UnexpectedEndOfTokens { line: 1, column: 2, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn parse_recovering_inserts_error_nodes() {
+        let source = "x: integer = ; f: function void () = { y = ; print y; } z: integer;";
+        let tokens = scanner::tokenize(source).unwrap();
+        let (declaration, errors) = parse_recovering(&tokens);
+        assert_eq!(errors.len(), 2);
+        let declaration = declaration.unwrap();
+        assert_eq!(declaration.type_.kind, TypeKind::Error);
+        let function = declaration.next.unwrap();
+        assert_eq!(function.name, "f");
+        let statement = function.code.unwrap().next_statement.unwrap();
+        assert_eq!(statement.kind, StatementKind::Error);
+        assert_eq!(statement.next_statement.unwrap().kind, StatementKind::Print);
+        assert_eq!(function.next.unwrap().name, "z");
+    }
+
+    #[test]
+    fn parse_recovering_after_missing_semicolon() {
+        let tokens = scanner::tokenize("x: integer = 5\ny: integer = ;\nz: integer = ;").unwrap();
+        let (_, errors) = parse_recovering(&tokens);
+        let locations: Vec<(usize, usize)> = errors.iter().map(ParseError::location).collect();
+        assert_eq!(locations, [(2, 1), (2, 14), (3, 14)]);
+        let source = "f: function void (a integer, b: integer);\ng: integer;";
+        let tokens = scanner::tokenize(source).unwrap();
+        let (declaration, errors) = parse_recovering(&tokens);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(declaration.unwrap().next.unwrap().name, "g");
+    }
 }
@@ -1,29 +1,119 @@
-use regex::Regex;
+use crate::source::{self, Span};
+use std::error::Error;
 use std::fmt;
 
+/// Scanning error located at the line and column where `span` starts.
 #[derive(Debug)]
 pub enum ScanError {
-    CannotScanToken { line: usize, column: usize },
-    UnclosedMultiLineComment { line: usize, column: usize },
+    CannotScanToken { line: usize, column: usize, span: Span },
+    UnclosedMultiLineComment { line: usize, column: usize, span: Span },
+    InvalidEscapeSequence { line: usize, column: usize, span: Span },
+    TextTooLong { line: usize, column: usize, span: Span },
+    FloatsNotEnabled { line: usize, column: usize, span: Span },
+}
+
+impl ScanError {
+    /// Name of the error variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ScanError::CannotScanToken { .. } => "CannotScanToken",
+            ScanError::UnclosedMultiLineComment { .. } => "UnclosedMultiLineComment",
+            ScanError::InvalidEscapeSequence { .. } => "InvalidEscapeSequence",
+            ScanError::TextTooLong { .. } => "TextTooLong",
+            ScanError::FloatsNotEnabled { .. } => "FloatsNotEnabled",
+        }
+    }
+
+    /// Line and column at which the error occurred.
+    pub fn location(&self) -> (usize, usize) {
+        match self {
+            ScanError::CannotScanToken { line, column, .. }
+            | ScanError::UnclosedMultiLineComment { line, column, .. }
+            | ScanError::InvalidEscapeSequence { line, column, .. }
+            | ScanError::TextTooLong { line, column, .. }
+            | ScanError::FloatsNotEnabled { line, column, .. } => (*line, *column),
+        }
+    }
+
+    /// Source range the error refers to.
+    pub fn span(&self) -> Span {
+        match self {
+            ScanError::CannotScanToken { span, .. }
+            | ScanError::UnclosedMultiLineComment { span, .. }
+            | ScanError::InvalidEscapeSequence { span, .. }
+            | ScanError::TextTooLong { span, .. }
+            | ScanError::FloatsNotEnabled { span, .. } => *span,
+        }
+    }
+}
+
+impl fmt::Display for ScanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanError::CannotScanToken { line, column, .. } => {
+                write!(f, "Cannot scan token at line {} column {}", line, column)
+            }
+            ScanError::UnclosedMultiLineComment { line, column, .. } => write!(
+                f,
+                "Multi-line comment at line {} column {} is never closed",
+                line, column
+            ),
+            ScanError::InvalidEscapeSequence { line, column, .. } => write!(
+                f,
+                "Invalid escape sequence at line {} column {}",
+                line, column
+            ),
+            ScanError::TextTooLong { line, column, .. } => write!(
+                f,
+                "String literal at line {} column {} is longer than {} characters",
+                line, column, MAX_TEXT_LENGTH
+            ),
+            ScanError::FloatsNotEnabled { line, column, .. } => write!(
+                f,
+                "Floating point literal at line {} column {} requires the float extension",
+                line, column
+            ),
+        }
+    }
+}
+
+impl Error for ScanError {}
+
+/// Language extensions that are not a part of B-Minor. All of them are
+/// disabled by default.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Extensions {
+    /// Enables the `float` type and floating point literals.
+    pub floats: bool,
+    /// Allows block comments to be nested, e.g. `/* /* */ */`.
+    pub nested_comments: bool,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq)]
-pub struct Token {
-    pub lexeme: String,
+pub struct Token<'src> {
+    pub lexeme: &'src str,
     pub category: Category,
     pub line: usize,
     pub column: usize,
+    pub span: Span,
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum Category {
     ArrayKeyword,
-    FunctionKeyword,
+    BooleanKeyword,
+    CharKeyword,
+    ElseKeyword,
+    FloatKeyword,
     ForKeyword,
+    FunctionKeyword,
     IfKeyword,
-    ElseKeyword,
-    ReturnKeyword,
+    IntegerKeyword,
     PrintKeyword,
+    ReturnKeyword,
+    StringKeyword,
+    VoidKeyword,
+    WhileKeyword,
     Identifier,
     Float,
     Integer,
@@ -31,8 +121,12 @@ pub enum Category {
     Character,
     Text,
     Equal,
+    DoubleEqual,
+    NotEqual,
     Plus,
+    DoublePlus,
     Minus,
+    DoubleMinus,
     Slash,
     Star,
     OpenBrace,
@@ -42,350 +136,884 @@ pub enum Category {
     OpenBracket,
     CloseBracket,
     Less,
+    LessEqual,
     More,
+    MoreEqual,
     Ampersand,
+    DoubleAmpersand,
     Pipe,
+    DoublePipe,
     Percent,
     Colon,
     Semicolon,
     Comma,
     Dash,
     Exclamation,
+    /// Empty token closing a trivia token stream, carrying the trivia found
+    /// after the last token.
+    EndOfFile,
+}
+
+/// Parts of the source code that are not tokens. Only produced by
+/// [`tokenize_with_trivia`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TriviaKind {
+    ByteOrderMark,
+    Whitespace,
+    Newline,
+    LineComment,
+    BlockComment,
+    /// `///` line comment or `/** */` block comment documenting the
+    /// declaration that follows it.
+    DocComment,
+    /// Source code that could not be scanned.
+    Skipped,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Trivia<'src> {
+    pub text: &'src str,
+    pub kind: TriviaKind,
+    pub span: Span,
+}
+
+/// Token together with the trivia surrounding it. Trailing trivia spans up to
+/// the end of the line of the token, everything else before the next token is
+/// its leading trivia.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TriviaToken<'src> {
+    pub leading: Vec<Trivia<'src>>,
+    pub token: Token<'src>,
+    pub trailing: Vec<Trivia<'src>>,
+}
+
+impl Category {
+    /// Returns the lexeme of the category if all of its tokens share it.
+    pub fn fixed_lexeme(&self) -> Option<&'static str> {
+        let lexeme = match self {
+            Category::ArrayKeyword => "array",
+            Category::BooleanKeyword => "boolean",
+            Category::CharKeyword => "char",
+            Category::ElseKeyword => "else",
+            Category::FloatKeyword => "float",
+            Category::ForKeyword => "for",
+            Category::FunctionKeyword => "function",
+            Category::IfKeyword => "if",
+            Category::IntegerKeyword => "integer",
+            Category::PrintKeyword => "print",
+            Category::ReturnKeyword => "return",
+            Category::StringKeyword => "string",
+            Category::VoidKeyword => "void",
+            Category::WhileKeyword => "while",
+            Category::Identifier
+            | Category::Float
+            | Category::Integer
+            | Category::Boolean
+            | Category::Character
+            | Category::Text
+            | Category::EndOfFile => return None,
+            Category::Equal => "=",
+            Category::DoubleEqual => "==",
+            Category::NotEqual => "!=",
+            Category::Plus => "+",
+            Category::DoublePlus => "++",
+            Category::Minus => "-",
+            Category::DoubleMinus => "--",
+            Category::Slash => "/",
+            Category::Star => "*",
+            Category::OpenBrace => "{",
+            Category::CloseBrace => "}",
+            Category::OpenParen => "(",
+            Category::CloseParen => ")",
+            Category::OpenBracket => "[",
+            Category::CloseBracket => "]",
+            Category::Less => "<",
+            Category::LessEqual => "<=",
+            Category::More => ">",
+            Category::MoreEqual => ">=",
+            Category::Ampersand => "&",
+            Category::DoubleAmpersand => "&&",
+            Category::Pipe => "|",
+            Category::DoublePipe => "||",
+            Category::Percent => "%",
+            Category::Colon => ":",
+            Category::Semicolon => ";",
+            Category::Comma => ",",
+            Category::Dash => "^",
+            Category::Exclamation => "!",
+        };
+        Some(lexeme)
+    }
+
+    /// Tells whether the category is a keyword. The boolean literals are
+    /// looked up among the keywords but are literals.
+    pub fn is_keyword(&self) -> bool {
+        let keyword = KEYWORDS.iter().any(|(_, category)| category == self);
+        (keyword && *self != Category::Boolean) || *self == Category::FloatKeyword
+    }
 }
 
 impl fmt::Display for Category {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            category => write!(f, "{}", category,),
+            Category::Identifier => write!(f, "identifier"),
+            Category::Float => write!(f, "float literal"),
+            Category::Integer => write!(f, "integer literal"),
+            Category::Boolean => write!(f, "boolean literal"),
+            Category::Character => write!(f, "character literal"),
+            Category::Text => write!(f, "string literal"),
+            Category::EndOfFile => write!(f, "end of file"),
+            category if category.is_keyword() => {
+                write!(f, "keyword '{}'", category.fixed_lexeme().unwrap())
+            }
+            category => write!(f, "'{}'", category.fixed_lexeme().unwrap()),
+        }
+    }
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.category {
+            Category::Identifier | Category::Float | Category::Integer | Category::Boolean => {
+                write!(f, "{} '{}'", self.category, self.lexeme)?
+            }
+            Category::Character | Category::Text => write!(f, "{} {}", self.category, self.lexeme)?,
+            category => write!(f, "{}", category)?,
         }
+        write!(f, " at line {} column {}", self.line, self.column)
     }
 }
 
-struct CharacterStream {
+struct CharacterStream<'src> {
     current_line: usize,
     current_column: usize,
-    stream: String,
+    stream: &'src str,
     current_index: usize,
 }
 
-impl CharacterStream {
-    pub fn new(stream: &str) -> Self {
+impl<'src> CharacterStream<'src> {
+    pub fn new(stream: &'src str) -> Self {
         CharacterStream {
             current_line: 1,
             current_column: 1,
-            stream: String::from(stream),
-            current_index: 0,
+            stream,
+            // byte order mark is not a part of the source code
+            current_index: if stream.starts_with(BYTE_ORDER_MARK) { 3 } else { 0 },
         }
     }
     pub fn consume(&mut self, amount: usize) {
-        let mut current_line = self.current_line;
-        let mut current_column = self.current_column;
-        for c in self.get_remaining()[..amount].chars() {
+        let (line, column) = self.location(amount);
+        self.current_line = line;
+        self.current_column = column;
+        self.current_index += amount;
+    }
+    /// Returns the line and column of the byte that is `offset` bytes past
+    /// the current position.
+    /// Returns the span of `length` bytes starting `offset` bytes past the
+    /// current position.
+    pub fn span(&self, offset: usize, length: usize) -> Span {
+        let start = self.current_index + offset;
+        Span::new(start, start + length)
+    }
+
+    pub fn location(&self, offset: usize) -> (usize, usize) {
+        let mut line = self.current_line;
+        let mut column = self.current_column;
+        for (index, c) in self.get_remaining()[..offset].char_indices() {
             match c {
-                '\n' => {
-                    current_line += 1;
-                    current_column = 1;
-                }
-                '\t' => {
-                    current_column += 4;
-                }
-                _ => {
-                    current_column += 1;
+                '\r' if self.peek(index + 1) == Some(b'\n') => (),
+                '\n' | '\r' => {
+                    line += 1;
+                    column = 1;
                 }
+                _ => column = source::advance_column(column, c),
             }
         }
-        self.current_line = current_line;
-        self.current_column = current_column;
-        self.current_index += amount;
+        (line, column)
+    }
+    pub fn get_remaining(&self) -> &'src str {
+        &self.stream[self.current_index..]
+    }
+    /// Returns the byte that is `n_ahead` bytes past the current position.
+    pub fn peek(&self, n_ahead: usize) -> Option<u8> {
+        self.stream.as_bytes().get(self.current_index + n_ahead).copied()
     }
-    pub fn get_remaining<'a>(&'a self) -> &'a str {
-        &self.stream.as_str()[self.current_index..]
-    }
-}
-
-const SCAN_FUNCTIONS: [fn(&mut CharacterStream) -> Option<Token>; 33] = [
-    try_array_keyword,
-    try_function_keyword,
-    try_for_keyword,
-    try_if_keyword,
-    try_else_keyword,
-    try_return_keyword,
-    try_print_keyword,
-    try_identifier,
-    try_float,
-    try_integer,
-    try_character,
-    try_text,
-    try_equal,
-    try_plus,
-    try_minus,
-    try_slash,
-    try_star,
-    try_open_paren,
-    try_close_paren,
-    try_open_bracket,
-    try_close_bracket,
-    try_open_brace,
-    try_close_brace,
-    try_less,
-    try_more,
-    try_ampersand,
-    try_pipe,
-    try_percent,
-    try_colon,
-    try_semicolon,
-    try_comma,
-    try_exclamation,
-    try_dash,
+    pub fn is_empty(&self) -> bool {
+        self.current_index == self.stream.len()
+    }
+}
+
+const BYTE_ORDER_MARK: char = '\u{feff}';
+
+/// Maximal number of characters in a string literal after decoding escapes.
+const MAX_TEXT_LENGTH: usize = 255;
+
+const KEYWORDS: [(&str, Category); 15] = [
+    ("array", Category::ArrayKeyword),
+    ("boolean", Category::BooleanKeyword),
+    ("char", Category::CharKeyword),
+    ("else", Category::ElseKeyword),
+    ("false", Category::Boolean),
+    ("for", Category::ForKeyword),
+    ("function", Category::FunctionKeyword),
+    ("if", Category::IfKeyword),
+    ("integer", Category::IntegerKeyword),
+    ("print", Category::PrintKeyword),
+    ("return", Category::ReturnKeyword),
+    ("string", Category::StringKeyword),
+    ("true", Category::Boolean),
+    ("void", Category::VoidKeyword),
+    ("while", Category::WhileKeyword),
 ];
 
-pub fn tokenize(stream: &str) -> Result<Vec<Token>, ScanError> {
+/// Iterator over the tokens of a source code. Lexemes of the tokens are
+/// borrowed from the source code. Once an error is returned the erroneous
+/// part of the source code is skipped so the iteration can continue.
+pub struct Lexer<'src> {
+    stream: CharacterStream<'src>,
+    extensions: Extensions,
+}
+
+impl<'src> Lexer<'src> {
+    pub fn new(stream: &'src str) -> Self {
+        Lexer::with_extensions(stream, Extensions::default())
+    }
+
+    pub fn with_extensions(stream: &'src str, extensions: Extensions) -> Self {
+        Lexer {
+            stream: CharacterStream::new(stream),
+            extensions,
+        }
+    }
+}
+
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Result<Token<'src>, ScanError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match next_token(&mut self.stream, self.extensions) {
+            Ok(token) => token.map(Ok),
+            Err(error) => {
+                let length = recovery_length(&self.stream);
+                self.stream.consume(length);
+                Some(Err(error))
+            }
+        }
+    }
+}
+
+pub fn tokenize(stream: &str) -> Result<Vec<Token<'_>>, ScanError> {
+    tokenize_with_extensions(stream, Extensions::default())
+}
+
+pub fn tokenize_with_extensions(
+    stream: &str,
+    extensions: Extensions,
+) -> Result<Vec<Token<'_>>, ScanError> {
+    Lexer::with_extensions(stream, extensions).collect()
+}
+
+/// Tokenizes the whole stream, skipping over the parts that cannot be
+/// scanned instead of stopping at the first of them. Returns all scanned
+/// tokens together with all encountered errors.
+pub fn tokenize_recovering(
+    stream: &str,
+    extensions: Extensions,
+) -> (Vec<Token<'_>>, Vec<ScanError>) {
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    for result in Lexer::with_extensions(stream, extensions) {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(error) => errors.push(error),
+        }
+    }
+    (tokens, errors)
+}
+
+/// Tokenizes the whole stream keeping all the trivia, so that the source code
+/// can be reproduced byte-for-byte with [`to_source`]. The last token is always
+/// of the `EndOfFile` category. Parts that cannot be scanned are kept as
+/// skipped trivia and reported as errors.
+pub fn tokenize_with_trivia(
+    stream: &str,
+    extensions: Extensions,
+) -> (Vec<TriviaToken<'_>>, Vec<ScanError>) {
     let mut stream = CharacterStream::new(stream);
     let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    let mut leading = Vec::new();
+    if stream.current_index > 0 {
+        let text = &stream.stream[..stream.current_index];
+        let span = Span::new(0, stream.current_index);
+        leading.push(Trivia { text, kind: TriviaKind::ByteOrderMark, span });
+    }
     loop {
-        // Checking for whitespace
-        if stream.get_remaining().starts_with(' ')
-            || stream.get_remaining().starts_with('\n')
-            || stream.get_remaining().starts_with("\r\n")
-            || stream.get_remaining().starts_with('\t')
-        {
-            stream.consume(1);
-            continue;
+        collect_trivia(&mut stream, extensions, &mut leading, &mut errors, false);
+        if stream.is_empty() {
+            let token = make_token(&mut stream, 0, Category::EndOfFile);
+            tokens.push(TriviaToken { leading, token, trailing: Vec::new() });
+            return (tokens, errors);
         }
-        // Checking for single-line comments
-        if stream.get_remaining().starts_with("//") {
-            stream.consume(2);
-            while !stream.get_remaining().starts_with("\n") {
-                stream.consume(1);
-            }
-            stream.consume(1);
-            continue;
-        }
-        // Checking for multi-line comments
-        if stream.get_remaining().starts_with("/*") {
-            let line = stream.current_line;
-            let column = stream.current_column;
-            stream.consume(2);
-            while !stream.get_remaining().starts_with("*/") {
-                if stream.get_remaining().len() == 0 {
-                    return Err(ScanError::UnclosedMultiLineComment { line, column });
-                }
-                stream.consume(1);
+        match scan_token(&stream, extensions) {
+            Ok((length, category)) => {
+                let token = make_token(&mut stream, length, category);
+                let mut trailing = Vec::new();
+                collect_trivia(&mut stream, extensions, &mut trailing, &mut errors, true);
+                let leading = std::mem::take(&mut leading);
+                tokens.push(TriviaToken { leading, token, trailing });
             }
-            stream.consume(2);
-            continue;
-        }
-        let mut scanned = false;
-        for scanner in &SCAN_FUNCTIONS[..] {
-            match scanner(&mut stream) {
-                Some(token) => {
-                    tokens.push(token);
-                    scanned = true;
-                    break;
-                }
-                None => (),
+            Err(error) => {
+                errors.push(error);
+                let length = recovery_length(&stream);
+                leading.push(make_trivia(&mut stream, length, TriviaKind::Skipped));
             }
         }
-        if scanned {
-            continue;
-        };
-        if !stream.get_remaining().is_empty() {
-            let line = stream.current_line;
-            let column = stream.current_column;
-            return Err(ScanError::CannotScanToken { line, column });
-        }
-        break;
     }
-    Ok(tokens)
 }
 
-fn make_token_scanner(
-    pattern: &'static str,
-    resulting_category: Category,
-) -> impl Fn(&mut CharacterStream) -> Option<Token> {
-    move |stream: &mut CharacterStream| {
-        let re = Regex::new(pattern).unwrap();
-        let remaining = stream.get_remaining();
-        let re_match = re.find(remaining)?;
-        let lexeme = String::from(re_match.as_str());
-        let line = stream.current_line;
-        let column = stream.current_column;
-        let category = resulting_category;
-        stream.consume(lexeme.len());
-        Some(Token {
-            lexeme,
-            line,
-            column,
-            category,
-        })
+/// Concatenates the tokens together with their trivia.
+pub fn to_source(tokens: &[TriviaToken]) -> String {
+    let mut source = String::new();
+    for token in tokens {
+        for trivia in &token.leading {
+            source.push_str(trivia.text);
+        }
+        source.push_str(token.token.lexeme);
+        for trivia in &token.trailing {
+            source.push_str(trivia.text);
+        }
     }
+    source
 }
 
-fn try_array_keyword(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^array", Category::ArrayKeyword);
-    scan(stream)
-}
-fn try_function_keyword(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^function", Category::FunctionKeyword);
-    scan(stream)
-}
-fn try_for_keyword(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^for", Category::ForKeyword);
-    scan(stream)
+/// Returns the text of the doc comments among the trivia with the comment
+/// markers stripped or `None` if there are no doc comments.
+pub fn doc_text(trivia: &[Trivia]) -> Option<String> {
+    let mut lines = Vec::new();
+    for trivia in trivia.iter().filter(|trivia| trivia.kind == TriviaKind::DocComment) {
+        if let Some(text) = trivia.text.strip_prefix("///") {
+            lines.push(text.strip_prefix(' ').unwrap_or(text));
+            continue;
+        }
+        let text = &trivia.text[3..trivia.text.len() - 2];
+        let block: Vec<&str> = text
+            .lines()
+            .map(|line| {
+                let line = line.trim();
+                let line = line.strip_prefix('*').unwrap_or(line);
+                line.strip_prefix(' ').unwrap_or(line).trim_end()
+            })
+            .collect();
+        let first = block.iter().position(|line| !line.is_empty());
+        let last = block.iter().rposition(|line| !line.is_empty());
+        if let (Some(first), Some(last)) = (first, last) {
+            lines.extend_from_slice(&block[first..=last]);
+        }
+    }
+    if lines.is_empty() {
+        None
+    } else {
+        Some(lines.join("\n"))
+    }
 }
 
-fn try_if_keyword(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^if", Category::IfKeyword);
-    scan(stream)
-}
-fn try_else_keyword(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^else", Category::ElseKeyword);
-    scan(stream)
-}
-fn try_return_keyword(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^return", Category::ReturnKeyword);
-    scan(stream)
-}
-fn try_print_keyword(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^print", Category::PrintKeyword);
-    scan(stream)
+fn collect_trivia<'src>(
+    stream: &mut CharacterStream<'src>,
+    extensions: Extensions,
+    trivia: &mut Vec<Trivia<'src>>,
+    errors: &mut Vec<ScanError>,
+    until_newline: bool,
+) {
+    loop {
+        match scan_trivia(stream, extensions) {
+            Ok(Some((_, TriviaKind::Newline))) if until_newline => return,
+            Ok(Some((length, kind))) => trivia.push(make_trivia(stream, length, kind)),
+            Ok(None) => return,
+            Err(error) => {
+                errors.push(error);
+                let length = recovery_length(stream);
+                trivia.push(make_trivia(stream, length, TriviaKind::Skipped));
+            }
+        }
+    }
 }
 
-fn try_identifier(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^[a-zA-Z_][a-zA-Z0-9_]*", Category::Identifier);
-    scan(stream)
+fn next_token<'src>(
+    stream: &mut CharacterStream<'src>,
+    extensions: Extensions,
+) -> Result<Option<Token<'src>>, ScanError> {
+    skip_whitespace_and_comments(stream, extensions)?;
+    if stream.is_empty() {
+        return Ok(None);
+    }
+    let (length, category) = scan_token(stream, extensions)?;
+    Ok(Some(make_token(stream, length, category)))
 }
 
-fn try_float(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^[0-9]+\.[0-9]*", Category::Float);
-    scan(stream)
+/// Returns the length of the erroneous input at the beginning of the
+/// stream that has to be skipped to continue scanning after an error.
+fn recovery_length(stream: &CharacterStream) -> usize {
+    let remaining = stream.get_remaining();
+    let bytes = remaining.as_bytes();
+    match bytes[0] {
+        // unclosed multi-line comment spans the rest of the stream
+        b'/' => remaining.len(),
+        // skip the whole literal up to its closing quote or the end of line
+        quote @ b'\'' | quote @ b'"' => {
+            let mut index = 1;
+            while index < bytes.len() && bytes[index] != b'\n' && bytes[index] != b'\r' {
+                match bytes[index] {
+                    b'\\' if index + 1 < bytes.len() && bytes[index + 1] != b'\n' => index += 2,
+                    c if c == quote => return index + 1,
+                    _ => index += 1,
+                }
+            }
+            index
+        }
+        b'0'..=b'9' => {
+            let number = remaining
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.' || c == '_'))
+                .unwrap_or(remaining.len());
+            number.max(1)
+        }
+        _ => remaining.chars().next().unwrap().len_utf8(),
+    }
 }
 
-fn try_integer(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^[0-9]+", Category::Integer);
-    scan(stream)
+fn skip_whitespace_and_comments(
+    stream: &mut CharacterStream,
+    extensions: Extensions,
+) -> Result<(), ScanError> {
+    while let Some((length, _)) = scan_trivia(stream, extensions)? {
+        stream.consume(length);
+    }
+    Ok(())
 }
 
-fn try_character(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r#"^'(\\)?[[:alpha:][0-9]]'"#, Category::Character);
-    scan(stream)
+fn scan_trivia(
+    stream: &CharacterStream,
+    extensions: Extensions,
+) -> Result<Option<(usize, TriviaKind)>, ScanError> {
+    let remaining = stream.get_remaining();
+    let trivia = match (stream.peek(0), stream.peek(1)) {
+        (Some(b'\r'), Some(b'\n')) => (2, TriviaKind::Newline),
+        (Some(b'\n'), _) | (Some(b'\r'), _) => (1, TriviaKind::Newline),
+        (Some(b' '), _) | (Some(b'\t'), _) | (Some(b'\x0c'), _) => {
+            let length = remaining
+                .find(|c| c != ' ' && c != '\t' && c != '\x0c')
+                .unwrap_or(remaining.len());
+            (length, TriviaKind::Whitespace)
+        }
+        // Checking for single-line comments
+        (Some(b'/'), Some(b'/')) => {
+            let length = remaining.find(&['\n', '\r'][..]).unwrap_or(remaining.len());
+            if remaining.starts_with("///") && !remaining.starts_with("////") {
+                (length, TriviaKind::DocComment)
+            } else {
+                (length, TriviaKind::LineComment)
+            }
+        }
+        // Checking for multi-line comments
+        (Some(b'/'), Some(b'*')) => match block_comment_length(remaining, extensions) {
+            Some(length) => {
+                let comment = &remaining[..length];
+                if comment.starts_with("/**") && !comment.starts_with("/***") && length > 4 {
+                    (length, TriviaKind::DocComment)
+                } else {
+                    (length, TriviaKind::BlockComment)
+                }
+            }
+            None => {
+                let line = stream.current_line;
+                let column = stream.current_column;
+                let span = stream.span(0, remaining.len());
+                return Err(ScanError::UnclosedMultiLineComment { line, column, span });
+            }
+        },
+        _ => return Ok(None),
+    };
+    Ok(Some(trivia))
 }
 
-fn try_text(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r#"^(?-m)("((\\.)|[^"\n])*")"#, Category::Text);
-    scan(stream)
+fn block_comment_length(remaining: &str, extensions: Extensions) -> Option<usize> {
+    if !extensions.nested_comments {
+        return remaining[2..].find("*/").map(|end| end + 4);
+    }
+    let bytes = remaining.as_bytes();
+    let mut depth = 1;
+    let mut index = 2;
+    while index + 1 < bytes.len() {
+        match (bytes[index], bytes[index + 1]) {
+            (b'/', b'*') => {
+                depth += 1;
+                index += 2;
+            }
+            (b'*', b'/') => {
+                depth -= 1;
+                index += 2;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => index += 1,
+        }
+    }
+    None
 }
 
-fn try_equal(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^=", Category::Equal);
-    scan(stream)
+fn scan_token(
+    stream: &CharacterStream,
+    extensions: Extensions,
+) -> Result<(usize, Category), ScanError> {
+    let remaining = stream.get_remaining().as_bytes();
+    let second = remaining.get(1);
+    let category = match remaining[0] {
+        b'a'..=b'z' | b'A'..=b'Z' | b'_' => return Ok(scan_word(remaining, extensions)),
+        b'0'..=b'9' => return scan_number(stream, extensions),
+        b'\'' => return scan_character(stream),
+        b'"' => return scan_text(stream),
+        b'=' if second == Some(&b'=') => return Ok((2, Category::DoubleEqual)),
+        b'!' if second == Some(&b'=') => return Ok((2, Category::NotEqual)),
+        b'<' if second == Some(&b'=') => return Ok((2, Category::LessEqual)),
+        b'>' if second == Some(&b'=') => return Ok((2, Category::MoreEqual)),
+        b'+' if second == Some(&b'+') => return Ok((2, Category::DoublePlus)),
+        b'-' if second == Some(&b'-') => return Ok((2, Category::DoubleMinus)),
+        b'&' if second == Some(&b'&') => return Ok((2, Category::DoubleAmpersand)),
+        b'|' if second == Some(&b'|') => return Ok((2, Category::DoublePipe)),
+        b'=' => Category::Equal,
+        b'+' => Category::Plus,
+        b'-' => Category::Minus,
+        b'/' => Category::Slash,
+        b'*' => Category::Star,
+        b'(' => Category::OpenParen,
+        b')' => Category::CloseParen,
+        b'[' => Category::OpenBracket,
+        b']' => Category::CloseBracket,
+        b'{' => Category::OpenBrace,
+        b'}' => Category::CloseBrace,
+        b'<' => Category::Less,
+        b'>' => Category::More,
+        b'&' => Category::Ampersand,
+        b'|' => Category::Pipe,
+        b'%' => Category::Percent,
+        b':' => Category::Colon,
+        b';' => Category::Semicolon,
+        b',' => Category::Comma,
+        b'!' => Category::Exclamation,
+        b'^' => Category::Dash,
+        _ => return Err(cannot_scan_token(stream)),
+    };
+    Ok((1, category))
 }
 
-fn try_plus(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\+", Category::Plus);
-    scan(stream)
+fn scan_word(remaining: &[u8], extensions: Extensions) -> (usize, Category) {
+    let length = remaining
+        .iter()
+        .position(|c| !(c.is_ascii_alphanumeric() || *c == b'_'))
+        .unwrap_or(remaining.len());
+    let word = &remaining[..length];
+    if extensions.floats && word == b"float" {
+        return (length, Category::FloatKeyword);
+    }
+    let category = KEYWORDS
+        .iter()
+        .find(|(keyword, _)| keyword.as_bytes() == word)
+        .map_or(Category::Identifier, |(_, category)| *category);
+    (length, category)
 }
 
-fn try_minus(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\-", Category::Minus);
-    scan(stream)
+fn scan_number(
+    stream: &CharacterStream,
+    extensions: Extensions,
+) -> Result<(usize, Category), ScanError> {
+    let remaining = stream.get_remaining().as_bytes();
+    let integer_length = count_digits(remaining);
+    let mut length = integer_length;
+    if remaining.get(length) == Some(&b'.') {
+        length += 1 + count_digits(&remaining[length + 1..]);
+    }
+    if extensions.floats {
+        length += count_exponent(&remaining[length..]);
+    }
+    if length == integer_length {
+        Ok((length, Category::Integer))
+    } else if extensions.floats {
+        Ok((length, Category::Float))
+    } else {
+        let line = stream.current_line;
+        let column = stream.current_column;
+        let span = stream.span(0, length);
+        Err(ScanError::FloatsNotEnabled { line, column, span })
+    }
 }
 
-fn try_slash(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^/", Category::Slash);
-    scan(stream)
+/// Returns the length of an `e` or `E` exponent with an optional sign,
+/// or zero if there is no such exponent.
+fn count_exponent(remaining: &[u8]) -> usize {
+    if !remaining.starts_with(b"e") && !remaining.starts_with(b"E") {
+        return 0;
+    }
+    let sign_length = match remaining.get(1) {
+        Some(b'+') | Some(b'-') => 1,
+        _ => 0,
+    };
+    match count_digits(&remaining[1 + sign_length..]) {
+        0 => 0,
+        digits => 1 + sign_length + digits,
+    }
 }
 
-fn try_star(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\*", Category::Star);
-    scan(stream)
+fn count_digits(remaining: &[u8]) -> usize {
+    remaining
+        .iter()
+        .position(|c| !c.is_ascii_digit())
+        .unwrap_or(remaining.len())
 }
 
-fn try_open_paren(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\(", Category::OpenParen);
-    scan(stream)
+fn scan_character(stream: &CharacterStream) -> Result<(usize, Category), ScanError> {
+    let remaining = stream.get_remaining();
+    if remaining[1..].starts_with('\'') {
+        return Err(cannot_scan_token(stream));
+    }
+    let length = 1 + scan_literal_character(stream, 1)?;
+    if !remaining[length..].starts_with('\'') {
+        return Err(cannot_scan_token(stream));
+    }
+    Ok((length + 1, Category::Character))
 }
 
-fn try_close_paren(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\)", Category::CloseParen);
-    scan(stream)
+fn scan_text(stream: &CharacterStream) -> Result<(usize, Category), ScanError> {
+    let remaining = stream.get_remaining();
+    let mut index = 1;
+    let mut characters = 0;
+    while !remaining[index..].starts_with('"') {
+        index += scan_literal_character(stream, index)?;
+        characters += 1;
+    }
+    if characters > MAX_TEXT_LENGTH {
+        let line = stream.current_line;
+        let column = stream.current_column;
+        let span = stream.span(0, index + 1);
+        return Err(ScanError::TextTooLong { line, column, span });
+    }
+    Ok((index + 1, Category::Text))
 }
 
-fn try_open_bracket(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\[", Category::OpenBracket);
-    scan(stream)
+/// Returns the length in bytes of a single, possibly escaped, character
+/// of a character or string literal that starts `offset` bytes past
+/// the current position.
+fn scan_literal_character(stream: &CharacterStream, offset: usize) -> Result<usize, ScanError> {
+    let mut characters = stream.get_remaining()[offset..].chars();
+    match characters.next() {
+        Some('\\') => match characters.next() {
+            Some(c) if !c.is_control() => Ok(1 + c.len_utf8()),
+            c => {
+                let (line, column) = stream.location(offset);
+                let span = stream.span(offset, 1 + c.map_or(0, char::len_utf8));
+                Err(ScanError::InvalidEscapeSequence { line, column, span })
+            }
+        },
+        Some(c) if !c.is_control() => Ok(c.len_utf8()),
+        _ => Err(cannot_scan_token(stream)),
+    }
 }
 
-fn try_close_bracket(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\]", Category::CloseBracket);
-    scan(stream)
+fn cannot_scan_token(stream: &CharacterStream) -> ScanError {
+    let line = stream.current_line;
+    let column = stream.current_column;
+    let length = stream.get_remaining().chars().next().map_or(0, char::len_utf8);
+    let span = stream.span(0, length);
+    ScanError::CannotScanToken { line, column, span }
 }
 
-fn try_open_brace(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\{", Category::OpenBrace);
-    scan(stream)
+/// Decodes the escape sequences of a character or string literal lexeme,
+/// stripping the surrounding quotes.
+pub fn unescape(lexeme: &str) -> String {
+    let mut decoded = String::new();
+    let mut characters = lexeme[1..lexeme.len() - 1].chars();
+    while let Some(c) = characters.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        match characters.next() {
+            Some('n') => decoded.push('\n'),
+            Some('0') => decoded.push('\0'),
+            Some(escaped) => decoded.push(escaped),
+            None => (),
+        }
+    }
+    decoded
 }
 
-fn try_close_brace(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^}", Category::CloseBrace);
-    scan(stream)
+fn make_token<'src>(
+    stream: &mut CharacterStream<'src>,
+    length: usize,
+    category: Category,
+) -> Token<'src> {
+    let lexeme = &stream.get_remaining()[..length];
+    let line = stream.current_line;
+    let column = stream.current_column;
+    let span = Span::new(stream.current_index, stream.current_index + length);
+    stream.consume(length);
+    Token {
+        lexeme,
+        line,
+        column,
+        span,
+        category,
+    }
 }
 
-fn try_less(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^<", Category::Less);
-    scan(stream)
+fn make_trivia<'src>(
+    stream: &mut CharacterStream<'src>,
+    length: usize,
+    kind: TriviaKind,
+) -> Trivia<'src> {
+    let text = &stream.get_remaining()[..length];
+    let span = Span::new(stream.current_index, stream.current_index + length);
+    stream.consume(length);
+    Trivia { text, kind, span }
 }
 
-fn try_more(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^>", Category::More);
-    scan(stream)
+pub fn print_pretty(tokens: &[Token]) {
+    print!("{}", dump(tokens, &[], DumpFormat::Table));
 }
 
-fn try_ampersand(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^&", Category::Ampersand);
-    scan(stream)
+/// Formats in which tokens and scanning errors can be dumped.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DumpFormat {
+    /// Fixed-width table meant to be read by humans.
+    Table,
+    /// Object with a `tokens` and an `errors` array.
+    Json,
+    /// One record per token followed by one record per error. The first
+    /// field tells which of them the record is.
+    Csv,
 }
 
-fn try_pipe(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\|", Category::Pipe);
-    scan(stream)
-}
+impl std::str::FromStr for DumpFormat {
+    type Err = String;
 
-fn try_percent(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^%", Category::Percent);
-    scan(stream)
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        match format {
+            "table" => Ok(DumpFormat::Table),
+            "json" => Ok(DumpFormat::Json),
+            "csv" => Ok(DumpFormat::Csv),
+            _ => Err(format!("unknown dump format {}", format)),
+        }
+    }
 }
 
-fn try_colon(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^:", Category::Colon);
-    scan(stream)
+/// Formats the category, lexeme, location and span of every token together
+/// with the kind and location of every error.
+pub fn dump(tokens: &[Token], errors: &[ScanError], format: DumpFormat) -> String {
+    match format {
+        DumpFormat::Table => dump_table(tokens, errors),
+        DumpFormat::Json => dump_json(tokens, errors),
+        DumpFormat::Csv => dump_csv(tokens, errors),
+    }
 }
 
-fn try_semicolon(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^;", Category::Semicolon);
-    scan(stream)
+fn dump_table(tokens: &[Token], errors: &[ScanError]) -> String {
+    let mut output = format!(
+        "{:4} {:3} {:11} {:20} {:15}\n",
+        "Line", "Col", "Span", "Lexeme", "Category"
+    );
+    for token in tokens {
+        let span = format!("{}..{}", token.span.start, token.span.end);
+        output.push_str(&format!(
+            "{:4} {:3} {:11} {:20} {:15?}\n",
+            token.line, token.column, span, token.lexeme, token.category
+        ));
+    }
+    if !errors.is_empty() {
+        output.push_str(&format!("\n{:4} {:3} {}\n", "Line", "Col", "Error"));
+        for error in errors {
+            let (line, column) = error.location();
+            output.push_str(&format!("{:4} {:3} {}\n", line, column, error.kind()));
+        }
+    }
+    output
 }
 
-fn try_comma(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^,", Category::Comma);
-    scan(stream)
+fn dump_json(tokens: &[Token], errors: &[ScanError]) -> String {
+    let tokens: Vec<String> = tokens
+        .iter()
+        .map(|token| {
+            format!(
+                "{{\"category\":\"{:?}\",\"lexeme\":{},\"line\":{},\"column\":{},\"span\":{{\"start\":{},\"end\":{}}}}}",
+                token.category,
+                json_string(token.lexeme),
+                token.line,
+                token.column,
+                token.span.start,
+                token.span.end
+            )
+        })
+        .collect();
+    let errors: Vec<String> = errors
+        .iter()
+        .map(|error| {
+            let (line, column) = error.location();
+            format!(
+                "{{\"error\":\"{}\",\"line\":{},\"column\":{}}}",
+                error.kind(),
+                line,
+                column
+            )
+        })
+        .collect();
+    format!(
+        "{{\"tokens\":[{}],\"errors\":[{}]}}\n",
+        tokens.join(","),
+        errors.join(",")
+    )
 }
 
-fn try_exclamation(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^!", Category::Exclamation);
-    scan(stream)
+fn json_string(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_control() => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
 }
 
-fn try_dash(stream: &mut CharacterStream) -> Option<Token> {
-    let scan = make_token_scanner(r"^\^", Category::Dash);
-    scan(stream)
+fn dump_csv(tokens: &[Token], errors: &[ScanError]) -> String {
+    let mut output = String::from("record,category,lexeme,line,column,start,end\n");
+    for token in tokens {
+        output.push_str(&format!(
+            "token,{:?},{},{},{},{},{}\n",
+            token.category,
+            csv_field(token.lexeme),
+            token.line,
+            token.column,
+            token.span.start,
+            token.span.end
+        ));
+    }
+    for error in errors {
+        let (line, column) = error.location();
+        output.push_str(&format!("error,{},,{},{},,\n", error.kind(), line, column));
+    }
+    output
 }
 
-pub fn print_pretty(tokens: &[Token]) {
-    println!("{:4} {:3} {:20} {:15}", "Line", "Col", "Lexeme", "Category");
-    for token in tokens {
-        //println!("{} {} {}", token.line, token.column, token.lexeme);
-        println!("{:4} {:3} {:20} {:15?}", token.line, token.column, token.lexeme, token.category);
+fn csv_field(text: &str) -> String {
+    if text.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        String::from(text)
     }
 }
 
@@ -397,231 +1025,265 @@ mod tests {
     fn tokenize_all_possible_tokens() {
         let stream = r#"_identifier1 1.25 19 'c' "string" = + - / * ( ) [ ] < > & | % : ; , ! ^ function for if else return print { } array"#;
         let identifier = Token {
-            lexeme: String::from("_identifier1"),
+            lexeme: "_identifier1",
             category: Category::Identifier,
             line: 1,
             column: 1,
+            span: Span::new(0, 12),
         };
         let float = Token {
-            lexeme: String::from("1.25"),
+            lexeme: "1.25",
             category: Category::Float,
             line: 1,
             column: 14,
+            span: Span::new(13, 17),
         };
         let integer = Token {
-            lexeme: String::from("19"),
+            lexeme: "19",
             category: Category::Integer,
             line: 1,
             column: 19,
+            span: Span::new(18, 20),
         };
         let character = Token {
-            lexeme: String::from("\'c\'"),
+            lexeme: "\'c\'",
             category: Category::Character,
             line: 1,
             column: 22,
+            span: Span::new(21, 24),
         };
         let string = Token {
-            lexeme: String::from("\"string\""),
+            lexeme: "\"string\"",
             category: Category::Text,
             line: 1,
             column: 26,
+            span: Span::new(25, 33),
         };
         let equal = Token {
-            lexeme: String::from("="),
+            lexeme: "=",
             category: Category::Equal,
             line: 1,
             column: 35,
+            span: Span::new(34, 35),
         };
         let plus = Token {
-            lexeme: String::from("+"),
+            lexeme: "+",
             category: Category::Plus,
             line: 1,
             column: 37,
+            span: Span::new(36, 37),
         };
 
         let minus = Token {
-            lexeme: String::from("-"),
+            lexeme: "-",
             category: Category::Minus,
             line: 1,
             column: 39,
+            span: Span::new(38, 39),
         };
 
         let slash = Token {
-            lexeme: String::from("/"),
+            lexeme: "/",
             category: Category::Slash,
             line: 1,
             column: 41,
+            span: Span::new(40, 41),
         };
 
         let star = Token {
-            lexeme: String::from("*"),
+            lexeme: "*",
             category: Category::Star,
             line: 1,
             column: 43,
+            span: Span::new(42, 43),
         };
 
         let open_paren = Token {
-            lexeme: String::from("("),
+            lexeme: "(",
             category: Category::OpenParen,
             line: 1,
             column: 45,
+            span: Span::new(44, 45),
         };
 
         let close_paren = Token {
-            lexeme: String::from(")"),
+            lexeme: ")",
             category: Category::CloseParen,
             line: 1,
             column: 47,
+            span: Span::new(46, 47),
         };
 
         let open_bracket = Token {
-            lexeme: String::from("["),
+            lexeme: "[",
             category: Category::OpenBracket,
             line: 1,
             column: 49,
+            span: Span::new(48, 49),
         };
 
         let close_bracket = Token {
-            lexeme: String::from("]"),
+            lexeme: "]",
             category: Category::CloseBracket,
             line: 1,
             column: 51,
+            span: Span::new(50, 51),
         };
 
         let less = Token {
-            lexeme: String::from("<"),
+            lexeme: "<",
             category: Category::Less,
             line: 1,
             column: 53,
+            span: Span::new(52, 53),
         };
 
         let more = Token {
-            lexeme: String::from(">"),
+            lexeme: ">",
             category: Category::More,
             line: 1,
             column: 55,
+            span: Span::new(54, 55),
         };
 
         let ampersand = Token {
-            lexeme: String::from("&"),
+            lexeme: "&",
             category: Category::Ampersand,
             line: 1,
             column: 57,
+            span: Span::new(56, 57),
         };
 
         let pipe = Token {
-            lexeme: String::from("|"),
+            lexeme: "|",
             category: Category::Pipe,
             line: 1,
             column: 59,
+            span: Span::new(58, 59),
         };
 
         let percent = Token {
-            lexeme: String::from("%"),
+            lexeme: "%",
             category: Category::Percent,
             line: 1,
             column: 61,
+            span: Span::new(60, 61),
         };
 
         let colon = Token {
-            lexeme: String::from(":"),
+            lexeme: ":",
             category: Category::Colon,
             line: 1,
             column: 63,
+            span: Span::new(62, 63),
         };
 
         let semicolon = Token {
-            lexeme: String::from(";"),
+            lexeme: ";",
             category: Category::Semicolon,
             line: 1,
             column: 65,
+            span: Span::new(64, 65),
         };
 
         let comma = Token {
-            lexeme: String::from(","),
+            lexeme: ",",
             category: Category::Comma,
             line: 1,
             column: 67,
+            span: Span::new(66, 67),
         };
 
         let exclamation = Token {
-            lexeme: String::from("!"),
+            lexeme: "!",
             category: Category::Exclamation,
             line: 1,
             column: 69,
+            span: Span::new(68, 69),
         };
 
         let dash = Token {
-            lexeme: String::from("^"),
+            lexeme: "^",
             category: Category::Dash,
             line: 1,
             column: 71,
+            span: Span::new(70, 71),
         };
 
         let function_keyword = Token {
-            lexeme: String::from("function"),
+            lexeme: "function",
             category: Category::FunctionKeyword,
             line: 1,
             column: 73,
+            span: Span::new(72, 80),
         };
 
         let for_keyword = Token {
-            lexeme: String::from("for"),
+            lexeme: "for",
             category: Category::ForKeyword,
             line: 1,
             column: 82,
+            span: Span::new(81, 84),
         };
 
         let if_keyword = Token {
-            lexeme: String::from("if"),
+            lexeme: "if",
             category: Category::IfKeyword,
             line: 1,
             column: 86,
+            span: Span::new(85, 87),
         };
 
         let else_keyword = Token {
-            lexeme: String::from("else"),
+            lexeme: "else",
             category: Category::ElseKeyword,
             line: 1,
             column: 89,
+            span: Span::new(88, 92),
         };
 
         let return_keyword = Token {
-            lexeme: String::from("return"),
+            lexeme: "return",
             category: Category::ReturnKeyword,
             line: 1,
             column: 94,
+            span: Span::new(93, 99),
         };
 
         let print_keyword = Token {
-            lexeme: String::from("print"),
+            lexeme: "print",
             category: Category::PrintKeyword,
             line: 1,
             column: 101,
+            span: Span::new(100, 105),
         };
 
         let open_brace = Token {
-            lexeme: String::from("{"),
+            lexeme: "{",
             category: Category::OpenBrace,
             line: 1,
             column: 107,
+            span: Span::new(106, 107),
         };
 
         let close_brace = Token {
-            lexeme: String::from("}"),
+            lexeme: "}",
             category: Category::CloseBrace,
             line: 1,
             column: 109,
+            span: Span::new(108, 109),
         };
 
         let array_keyword = Token {
-            lexeme: String::from("array"),
+            lexeme: "array",
             category: Category::ArrayKeyword,
             line: 1,
             column: 111,
+            span: Span::new(110, 115),
         };
 
-        let tokens = tokenize(stream).unwrap();
+        let extensions = Extensions { floats: true, ..Extensions::default() };
+        let tokens = tokenize_with_extensions(stream, extensions).unwrap();
         assert_eq!(tokens[0], identifier);
         assert_eq!(tokens[1], float);
         assert_eq!(tokens[2], integer);
@@ -656,4 +1318,389 @@ mod tests {
         assert_eq!(tokens[31], close_brace);
         assert_eq!(tokens[32], array_keyword);
     }
+
+    #[test]
+    fn tokenize_keywords() {
+        let stream = "array boolean char else false for function if integer print return string true void while";
+        let categories: Vec<Category> = tokenize(stream)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.category)
+            .collect();
+        let expected = [
+            Category::ArrayKeyword,
+            Category::BooleanKeyword,
+            Category::CharKeyword,
+            Category::ElseKeyword,
+            Category::Boolean,
+            Category::ForKeyword,
+            Category::FunctionKeyword,
+            Category::IfKeyword,
+            Category::IntegerKeyword,
+            Category::PrintKeyword,
+            Category::ReturnKeyword,
+            Category::StringKeyword,
+            Category::Boolean,
+            Category::VoidKeyword,
+            Category::WhileKeyword,
+        ];
+        assert_eq!(categories, expected);
+    }
+
+    #[test]
+    fn tokenize_identifiers_starting_with_keywords() {
+        let stream = "format iffy printer arrays _while voids";
+        let tokens = tokenize(stream).unwrap();
+        assert_eq!(tokens.len(), 6);
+        for (token, lexeme) in tokens.iter().zip(stream.split(' ')) {
+            assert_eq!(token.category, Category::Identifier);
+            assert_eq!(token.lexeme, lexeme);
+        }
+    }
+
+    #[test]
+    fn tokenize_compound_operators() {
+        let stream = "== != <= >= ++ -- && || = = ! = < = > = + + - - & & | | ===";
+        let categories: Vec<Category> = tokenize(stream)
+            .unwrap()
+            .into_iter()
+            .map(|token| token.category)
+            .collect();
+        let expected = [
+            Category::DoubleEqual,
+            Category::NotEqual,
+            Category::LessEqual,
+            Category::MoreEqual,
+            Category::DoublePlus,
+            Category::DoubleMinus,
+            Category::DoubleAmpersand,
+            Category::DoublePipe,
+            Category::Equal,
+            Category::Equal,
+            Category::Exclamation,
+            Category::Equal,
+            Category::Less,
+            Category::Equal,
+            Category::More,
+            Category::Equal,
+            Category::Plus,
+            Category::Plus,
+            Category::Minus,
+            Category::Minus,
+            Category::Ampersand,
+            Category::Ampersand,
+            Category::Pipe,
+            Category::Pipe,
+            Category::DoubleEqual,
+            Category::Equal,
+        ];
+        assert_eq!(categories, expected);
+    }
+
+    #[test]
+    fn tokenize_escape_sequences() {
+        let stream = r#"' ' '\n' '\0' '\\' '\'' '"' '.' '\c' "a \"quoted\" \\ \' \n \0 \x""#;
+        let decoded: Vec<String> = tokenize(stream)
+            .unwrap()
+            .iter()
+            .map(|token| unescape(token.lexeme))
+            .collect();
+        let expected = [" ", "\n", "\0", "\\", "'", "\"", ".", "c", "a \"quoted\" \\ ' \n \0 x"];
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn tokenize_invalid_literals() {
+        match tokenize("x = \"abc\\\n\";") {
+            Err(ScanError::InvalidEscapeSequence { line: 1, column: 9, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match tokenize("'\\\t'") {
+            Err(ScanError::InvalidEscapeSequence { line: 1, column: 2, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match tokenize(&format!("\n  \"{}\"", "a".repeat(MAX_TEXT_LENGTH + 1))) {
+            Err(ScanError::TextTooLong { line: 2, column: 3, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        assert!(tokenize(&format!("\"{}\"", "a".repeat(MAX_TEXT_LENGTH))).is_ok());
+        assert!(tokenize("''").is_err());
+        assert!(tokenize("'ab'").is_err());
+        assert!(tokenize("\"unterminated\\\"").is_err());
+    }
+
+    #[test]
+    fn tokenize_floats() {
+        let extensions = Extensions { floats: true, ..Extensions::default() };
+        let stream = "float 1.5e-3 2E10 3.e+2 4. 5.25 6e 7.5e+x";
+        let tokens: Vec<(Category, &str)> = tokenize_with_extensions(stream, extensions)
+            .unwrap()
+            .into_iter()
+            .map(|token| (token.category, token.lexeme))
+            .collect();
+        let expected = [
+            (Category::FloatKeyword, "float"),
+            (Category::Float, "1.5e-3"),
+            (Category::Float, "2E10"),
+            (Category::Float, "3.e+2"),
+            (Category::Float, "4."),
+            (Category::Float, "5.25"),
+            (Category::Integer, "6"),
+            (Category::Identifier, "e"),
+            (Category::Float, "7.5"),
+            (Category::Identifier, "e"),
+            (Category::Plus, "+"),
+            (Category::Identifier, "x"),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn scan_error_spans() {
+        let text = format!("\"{}\"", "a".repeat(MAX_TEXT_LENGTH + 1));
+        let cases = [
+            ("x = 1 @ 2;", Span::new(6, 7)),
+            ("x /* y", Span::new(2, 6)),
+            ("x = \"a\\\u{7}\";", Span::new(6, 8)),
+            (text.as_str(), Span::new(0, MAX_TEXT_LENGTH + 3)),
+            ("x = 1.5;", Span::new(4, 7)),
+        ];
+        for (stream, span) in cases.iter() {
+            match tokenize(stream) {
+                Err(error) => assert_eq!(error.span(), *span, "{}", error),
+                result => panic!("unexpected result {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn tokenize_floats_without_extension() {
+        match tokenize("x = 1.5;") {
+            Err(ScanError::FloatsNotEnabled { line: 1, column: 5, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        let categories: Vec<Category> = tokenize("float 1e5")
+            .unwrap()
+            .into_iter()
+            .map(|token| token.category)
+            .collect();
+        let expected = [Category::Identifier, Category::Integer, Category::Identifier];
+        assert_eq!(categories, expected);
+    }
+
+    #[test]
+    fn lexer_borrows_lexemes_and_continues_after_errors() {
+        let stream = "x = @ 'ab' y;";
+        let results: Vec<Result<Token, ScanError>> = Lexer::new(stream).collect();
+        assert_eq!(results.len(), 6);
+        match &results[0] {
+            Ok(token) => assert!(std::ptr::eq(token.lexeme.as_ptr(), stream.as_ptr())),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match &results[2] {
+            Err(ScanError::CannotScanToken { line: 1, column: 5, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match &results[3] {
+            Err(ScanError::CannotScanToken { line: 1, column: 7, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+        match &results[4] {
+            Ok(token) => assert_eq!(token.lexeme, "y"),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn tokenize_recovering_reports_all_errors() {
+        let stream = "x @ = 'ab' + 1.5;\n# \"bad \\\n y /* unclosed";
+        let (tokens, errors) = tokenize_recovering(stream, Extensions::default());
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme).collect();
+        assert_eq!(lexemes, ["x", "=", "+", ";", "y"]);
+        let locations: Vec<(usize, usize)> = errors.iter().map(ScanError::location).collect();
+        assert_eq!(locations, [(1, 3), (1, 7), (1, 14), (2, 1), (2, 8), (3, 4)]);
+    }
+
+    #[test]
+    fn tokenize_with_trivia_attaches_comments() {
+        let stream = "\u{feff}// header\nx: integer; /* x */ // end\r\n\ty\t@";
+        let (tokens, errors) = tokenize_with_trivia(stream, Extensions::default());
+        assert_eq!(to_source(&tokens), stream);
+        assert_eq!(errors.len(), 1);
+        let kinds = |trivia: &[Trivia]| -> Vec<TriviaKind> {
+            trivia.iter().map(|trivia| trivia.kind).collect()
+        };
+        let first = &tokens[0];
+        assert_eq!(first.token.lexeme, "x");
+        assert_eq!(
+            kinds(&first.leading),
+            [TriviaKind::ByteOrderMark, TriviaKind::LineComment, TriviaKind::Newline]
+        );
+        let semicolon = &tokens[3];
+        assert_eq!(semicolon.token.category, Category::Semicolon);
+        assert_eq!(
+            kinds(&semicolon.trailing),
+            [
+                TriviaKind::Whitespace,
+                TriviaKind::BlockComment,
+                TriviaKind::Whitespace,
+                TriviaKind::LineComment
+            ]
+        );
+        let last = tokens.last().unwrap();
+        assert_eq!(last.token.category, Category::EndOfFile);
+        assert_eq!(kinds(&last.leading), [TriviaKind::Skipped]);
+        assert_eq!(tokens[4].token.lexeme, "y");
+        assert_eq!(kinds(&tokens[4].leading), [TriviaKind::Newline, TriviaKind::Whitespace]);
+        assert_eq!(kinds(&tokens[4].trailing), [TriviaKind::Whitespace]);
+    }
+
+    #[test]
+    fn tokenize_nested_comments() {
+        let stream = "/* a /* b */ c */ x";
+        match tokenize(stream) {
+            Ok(tokens) => assert_eq!(tokens.len(), 4),
+            result => panic!("unexpected result {:?}", result),
+        }
+        let extensions = Extensions { nested_comments: true, ..Extensions::default() };
+        let tokens = tokenize_with_extensions(stream, extensions).unwrap();
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(tokens[0].lexeme, "x");
+        match tokenize_with_extensions("/* /* */ x", extensions) {
+            Err(ScanError::UnclosedMultiLineComment { line: 1, column: 1, .. }) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn tokenize_doc_comments() {
+        let stream = "/// Adds.\n/// Twice.\n//// plain\n/**/ /***/ /**\n * Block.\n */ x";
+        let (tokens, _) = tokenize_with_trivia(stream, Extensions::default());
+        let kinds: Vec<TriviaKind> = tokens[0]
+            .leading
+            .iter()
+            .map(|trivia| trivia.kind)
+            .filter(|kind| *kind != TriviaKind::Whitespace && *kind != TriviaKind::Newline)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                TriviaKind::DocComment,
+                TriviaKind::DocComment,
+                TriviaKind::LineComment,
+                TriviaKind::BlockComment,
+                TriviaKind::BlockComment,
+                TriviaKind::DocComment
+            ]
+        );
+        let doc = doc_text(&tokens[0].leading);
+        assert_eq!(doc.as_deref(), Some("Adds.\nTwice.\nBlock."));
+        assert_eq!(doc_text(&tokens[1].leading), None);
+    }
+
+    #[test]
+    fn dump_tokens_as_json() {
+        let (tokens, errors) = tokenize_recovering("s = \"a\\\"\\n\";\n@", Extensions::default());
+        let expected = concat!(
+            r#"{"tokens":["#,
+            r#"{"category":"Identifier","lexeme":"s","line":1,"column":1,"span":{"start":0,"end":1}},"#,
+            r#"{"category":"Equal","lexeme":"=","line":1,"column":3,"span":{"start":2,"end":3}},"#,
+            r#"{"category":"Text","lexeme":"\"a\\\"\\n\"","line":1,"column":5,"span":{"start":4,"end":11}},"#,
+            r#"{"category":"Semicolon","lexeme":";","line":1,"column":12,"span":{"start":11,"end":12}}"#,
+            r#"],"errors":[{"error":"CannotScanToken","line":2,"column":1}]}"#,
+            "\n"
+        );
+        assert_eq!(dump(&tokens, &errors, DumpFormat::Json), expected);
+    }
+
+    #[test]
+    fn dump_tokens_as_csv() {
+        let (tokens, errors) = tokenize_recovering("c = ',';\n\"\"\"", Extensions::default());
+        let expected = "record,category,lexeme,line,column,start,end\n\
+            token,Identifier,c,1,1,0,1\n\
+            token,Equal,=,1,3,2,3\n\
+            token,Character,\"','\",1,5,4,7\n\
+            token,Semicolon,;,1,8,7,8\n\
+            token,Text,\"\"\"\"\"\",2,1,9,11\n\
+            error,CannotScanToken,,2,3,,\n";
+        assert_eq!(dump(&tokens, &errors, DumpFormat::Csv), expected);
+        assert_eq!("csv".parse::<DumpFormat>(), Ok(DumpFormat::Csv));
+        assert!("xml".parse::<DumpFormat>().is_err());
+    }
+
+    #[test]
+    fn display_categories() {
+        assert_eq!(Category::OpenParen.to_string(), "'('");
+        assert_eq!(Category::DoubleAmpersand.to_string(), "'&&'");
+        assert_eq!(Category::Identifier.to_string(), "identifier");
+        assert_eq!(Category::Text.to_string(), "string literal");
+        assert_eq!(Category::ForKeyword.to_string(), "keyword 'for'");
+        assert_eq!(Category::FloatKeyword.to_string(), "keyword 'float'");
+        assert_eq!(Category::EndOfFile.to_string(), "end of file");
+        assert!(Category::WhileKeyword.is_keyword());
+        assert!(!Category::Boolean.is_keyword());
+    }
+
+    #[test]
+    fn display_tokens() {
+        let tokens = tokenize("x 42 'a' \"hi\" true while (").unwrap();
+        let messages: Vec<String> = tokens.iter().map(Token::to_string).collect();
+        assert_eq!(
+            messages,
+            [
+                "identifier 'x' at line 1 column 1",
+                "integer literal '42' at line 1 column 3",
+                "character literal 'a' at line 1 column 6",
+                "string literal \"hi\" at line 1 column 10",
+                "boolean literal 'true' at line 1 column 15",
+                "keyword 'while' at line 1 column 20",
+                "'(' at line 1 column 26",
+            ]
+        );
+    }
+
+    #[test]
+    fn display_scan_errors() {
+        let messages: Vec<String> = [
+            ScanError::CannotScanToken { line: 1, column: 2, span: Span::default() },
+            ScanError::UnclosedMultiLineComment { line: 3, column: 4, span: Span::default() },
+            ScanError::InvalidEscapeSequence { line: 5, column: 6, span: Span::default() },
+            ScanError::TextTooLong { line: 7, column: 8, span: Span::default() },
+            ScanError::FloatsNotEnabled { line: 9, column: 10, span: Span::default() },
+        ]
+        .iter()
+        .map(ScanError::to_string)
+        .collect();
+        assert_eq!(
+            messages,
+            [
+                "Cannot scan token at line 1 column 2",
+                "Multi-line comment at line 3 column 4 is never closed",
+                "Invalid escape sequence at line 5 column 6",
+                "String literal at line 7 column 8 is longer than 255 characters",
+                "Floating point literal at line 9 column 10 requires the float extension",
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenize_non_ascii_and_tabs() {
+        let stream = "s = \"żółw\";\n\tx\t=  'ą';";
+        let tokens = tokenize(stream).unwrap();
+        let locations: Vec<(usize, usize, Span)> = tokens
+            .iter()
+            .map(|token| (token.line, token.column, token.span))
+            .collect();
+        let expected = [
+            (1, 1, Span::new(0, 1)),
+            (1, 3, Span::new(2, 3)),
+            (1, 5, Span::new(4, 13)),
+            (1, 11, Span::new(13, 14)),
+            (2, 5, Span::new(16, 17)),
+            (2, 9, Span::new(18, 19)),
+            (2, 12, Span::new(21, 25)),
+            (2, 15, Span::new(25, 26)),
+        ];
+        assert_eq!(locations, expected);
+    }
 }
@@ -0,0 +1,159 @@
+/// Number of columns between two consecutive tab stops.
+pub const TAB_WIDTH: usize = 4;
+
+/// Range of bytes `start..end` in the source code.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end }
+    }
+
+    /// Returns the smallest span covering both spans.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+        }
+    }
+}
+
+/// Returns the column following the character `c` placed at `column`.
+pub fn advance_column(column: usize, c: char) -> usize {
+    match c {
+        '\t' => column + TAB_WIDTH - (column - 1) % TAB_WIDTH,
+        _ => column + 1,
+    }
+}
+
+/// Converts byte offsets in the source code into lines and columns.
+/// Lines and columns start at 1, columns count characters and tabs
+/// advance to the next tab stop. Lines end with `\n`, `\r\n` or `\r`.
+pub struct SourceMap<'a> {
+    source: &'a str,
+    line_starts: Vec<usize>,
+}
+
+impl<'a> SourceMap<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let bytes = source.as_bytes();
+        let mut line_starts = vec![0];
+        for (index, byte) in bytes.iter().enumerate() {
+            match byte {
+                b'\r' if bytes.get(index + 1) == Some(&b'\n') => (),
+                b'\n' | b'\r' => line_starts.push(index + 1),
+                _ => (),
+            }
+        }
+        SourceMap {
+            source,
+            line_starts,
+        }
+    }
+
+    /// Returns the line and column of the character at `offset`. Offsets
+    /// inside a multi-byte character are located at that character.
+    pub fn location(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.source.len());
+        let line = self.line_starts.partition_point(|start| *start <= offset);
+        let line_start = self.line_starts[line - 1];
+        let column = self.source[line_start..]
+            .char_indices()
+            .take_while(|(index, c)| line_start + index + c.len_utf8() <= offset)
+            .fold(1, |column, (_, c)| match c {
+                '\r' | '\u{feff}' => column,
+                _ => advance_column(column, c),
+            });
+        (line, column)
+    }
+
+    /// Returns the text of the line with the given number without the line
+    /// terminator.
+    pub fn line(&self, line: usize) -> &'a str {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |next| next - 1);
+        self.source[start..end].trim_end_matches('\r')
+    }
+
+    /// Returns the first line of the span followed by a line marking the
+    /// spanned characters with `^`.
+    pub fn underline(&self, span: Span) -> String {
+        let (line, _) = self.location(span.start);
+        let text = self.line(line);
+        let line_start = self.line_starts[line - 1];
+        let end = span.end.max(span.start + 1);
+        let mut marker = String::new();
+        for (index, c) in text.char_indices() {
+            let offset = line_start + index;
+            if offset >= end {
+                break;
+            } else if offset < span.start {
+                marker.push(if c == '\t' { '\t' } else { ' ' });
+            } else {
+                marker.push('^');
+            }
+        }
+        if !marker.ends_with('^') {
+            marker.push('^');
+        }
+        format!("{}\n{}", text, marker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_offsets() {
+        let source = "ab\n\tcd\r\nżółw x\n";
+        let map = SourceMap::new(source);
+        assert_eq!(map.location(0), (1, 1));
+        assert_eq!(map.location(2), (1, 3));
+        assert_eq!(map.location(3), (2, 1));
+        assert_eq!(map.location(4), (2, 5));
+        assert_eq!(map.location(8), (3, 1));
+        assert_eq!(map.location(9), (3, 1));
+        assert_eq!(map.location(10), (3, 2));
+        assert_eq!(map.location(16), (3, 6));
+        assert_eq!(map.location(source.len()), (4, 1));
+        assert_eq!(map.line(2), "\tcd");
+        assert_eq!(map.line(3), "żółw x");
+    }
+
+    #[test]
+    fn locate_offsets_with_other_line_endings() {
+        let source = "\u{feff}a\rb\r\nc";
+        let map = SourceMap::new(source);
+        assert_eq!(map.location(3), (1, 1));
+        assert_eq!(map.location(5), (2, 1));
+        assert_eq!(map.location(8), (3, 1));
+        assert_eq!(map.line(1), "\u{feff}a");
+        assert_eq!(map.line(2), "b");
+        assert_eq!(map.line(3), "c");
+    }
+
+    #[test]
+    fn advance_to_tab_stops() {
+        assert_eq!(advance_column(1, '\t'), 5);
+        assert_eq!(advance_column(3, '\t'), 5);
+        assert_eq!(advance_column(5, '\t'), 9);
+        assert_eq!(advance_column(5, 'x'), 6);
+    }
+
+    #[test]
+    fn underline_span() {
+        let source = "x = 1;\n\tżółw = 'c';\n";
+        let map = SourceMap::new(source);
+        assert_eq!(map.underline(Span::new(4, 5)), "x = 1;\n    ^");
+        let start = source.find('\'').unwrap();
+        assert_eq!(map.underline(Span::new(start, start + 3)), "\tżółw = 'c';\n\t       ^^^");
+    }
+}
@@ -1,7 +1,132 @@
+use rbm::ast::{
+    Declaration, Expression, ExpressionKind, ExpressionValue, Statement, StatementKind, TypeKind,
+};
 use rbm::parser;
 use rbm::scanner;
+use rbm::source::SourceMap;
 use std::fs;
 
+/// Asserts that the fields populated in every node of the AST agree with the
+/// kind of the node.
+fn assert_valid_declaration(declaration: &Declaration) {
+    let value = declaration.value.is_some();
+    let code = declaration.code.is_some();
+    match declaration.type_.kind {
+        TypeKind::Function => assert!(!value, "{:?}", declaration),
+        TypeKind::Error => assert!(!value && !code, "{:?}", declaration),
+        _ => assert!(!code, "{:?}", declaration),
+    }
+    if let Some(value) = &declaration.value {
+        assert_valid_expression(value);
+    }
+    if let Some(code) = &declaration.code {
+        assert_eq!(code.kind, StatementKind::Block);
+        assert_valid_statement(code);
+    }
+    if let Some(next) = &declaration.next {
+        assert_valid_declaration(next);
+    }
+}
+
+fn assert_valid_statement(statement: &Statement) {
+    let fields = (
+        statement.declaration.is_some(),
+        statement.expression.is_some(),
+        statement.for_initial_expr.is_some() || statement.for_next_expr.is_some(),
+        statement.body.is_some(),
+        statement.else_body.is_some(),
+        !statement.print_args.is_empty(),
+    );
+    let valid = match statement.kind {
+        StatementKind::Declaration => fields == (true, false, false, false, false, false),
+        StatementKind::Expression | StatementKind::Return => {
+            fields == (false, true, false, false, false, false)
+        }
+        StatementKind::IfElse => matches!(fields, (false, true, false, true, _, false)),
+        StatementKind::For => matches!(fields, (false, _, _, true, false, false)),
+        StatementKind::While => fields == (false, true, false, true, false, false),
+        StatementKind::Print => matches!(fields, (false, false, false, false, false, _)),
+        StatementKind::Block | StatementKind::Error => {
+            fields == (false, false, false, false, false, false)
+        }
+    };
+    assert!(valid, "{:?}", statement);
+    if let Some(declaration) = &statement.declaration {
+        assert_valid_declaration(declaration);
+    }
+    let expressions = [
+        &statement.expression,
+        &statement.for_initial_expr,
+        &statement.for_next_expr,
+    ];
+    for expression in expressions.iter().filter_map(|expression| expression.as_ref()) {
+        assert_valid_expression(expression);
+    }
+    statement.print_args.iter().for_each(assert_valid_expression);
+    let statements = [&statement.body, &statement.else_body, &statement.next_statement];
+    for statement in statements.iter().filter_map(|statement| statement.as_ref()) {
+        assert_valid_statement(statement);
+    }
+}
+
+fn assert_valid_expression(expression: &Expression) {
+    let left = expression.left.is_some();
+    let right = expression.right.is_some();
+    let valid = match (&expression.kind, &expression.value) {
+        (ExpressionKind::Identifier, Some(ExpressionValue::Name(_))) => !left && !right,
+        (
+            ExpressionKind::Literal,
+            Some(
+                ExpressionValue::Integer(_)
+                | ExpressionValue::Float(_)
+                | ExpressionValue::Boolean(_)
+                | ExpressionValue::Character(_)
+                | ExpressionValue::Text(_),
+            ),
+        ) => !left && !right,
+        (ExpressionKind::Array, Some(ExpressionValue::Array(values))) => {
+            values.iter().for_each(assert_valid_expression);
+            !left && !right
+        }
+        (ExpressionKind::FunctionCall, Some(ExpressionValue::Arguments(arguments))) => {
+            arguments.iter().for_each(assert_valid_expression);
+            left && !right
+        }
+        (
+            ExpressionKind::Minus
+            | ExpressionKind::Negation
+            | ExpressionKind::Incrementation
+            | ExpressionKind::Decrementation,
+            None,
+        ) => left && !right,
+        (
+            ExpressionKind::Assignment
+            | ExpressionKind::And
+            | ExpressionKind::Or
+            | ExpressionKind::Less
+            | ExpressionKind::LessEqual
+            | ExpressionKind::More
+            | ExpressionKind::MoreEqual
+            | ExpressionKind::Equal
+            | ExpressionKind::NotEqual
+            | ExpressionKind::Addition
+            | ExpressionKind::Subtraction
+            | ExpressionKind::Multiplication
+            | ExpressionKind::Division
+            | ExpressionKind::Modulo
+            | ExpressionKind::Power
+            | ExpressionKind::Subscript,
+            None,
+        ) => left && right,
+        _ => false,
+    };
+    assert!(valid, "{:?}", expression);
+    let operands = [&expression.left, &expression.right];
+    for operand in operands.iter().filter_map(|operand| operand.as_ref()) {
+        assert_valid_expression(operand);
+    }
+}
+
 #[test]
 fn test_parsing_good() {
     let directories = fs::read_dir("./tests/parser_programs/good").unwrap();
@@ -13,11 +138,10 @@ fn test_parsing_good() {
         let tokens = scanner::tokenize(content.as_str()).unwrap();
         println!("{}", content.as_str());
         let ast = parser::parse(&tokens);
-        let b = ast.is_ok();
-        if ast.is_err() {
-            println!("{:?}", ast.unwrap_err());
+        if let Err(err) = &ast {
+            println!("{:?}", err);
         }
-        assert!(b)
+        assert_valid_declaration(&ast.unwrap());
     }
 }
 
@@ -30,10 +154,257 @@ fn test_parsing_bad() {
         let content: String = fs::read_to_string(canonicalized).unwrap();
         let tokens = scanner::tokenize(content.as_str()).unwrap();
         let ast = parser::parse(&tokens);
-        let b = ast.is_err();
-        if ast.is_ok() {
-            println!("{:?}", ast.unwrap());
+        if let Ok(declaration) = &ast {
+            println!("{:?}", declaration);
+        }
+        assert!(ast.is_err());
+    }
+}
+
+/// Messages of all errors reported when recovering from them.
+const BAD_PROGRAM_ERRORS: [(&str, &[&str]); 21] = [
+    ("bad1.bminor", &["Unexpected '*' at line 4 column 20, expected an expression"]),
+    ("bad2.bminor", &["Unexpected ';' at line 4 column 5, expected '}' or a statement in block"]),
+    (
+        "bad3.bminor",
+        &[
+            "Unexpected string literal \"hello world\" at line 2 column 8, expected ':' after declaration name",
+        ],
+    ),
+    ("bad4.bminor", &["Unexpected ')' at line 4 column 17, expected ';' after for loop condition"]),
+    (
+        "bad5.bminor",
+        &[
+            "Type identifier intger at line 2 column 3 is unknown",
+            "Unexpected '{' at line 4 column 1, expected '=' or ';' after function type",
+        ],
+    ),
+    (
+        "bad6.bminor",
+        &[
+            "Unexpected '}' at line 8 column 1, expected ';' after expression statement",
+        ],
+    ),
+    ("bad7.bminor", &["Unexpected '}' at line 7 column 1, expected a statement"]),
+    (
+        "bad8.bminor",
+        &[
+            "Unexpected string literal \"hello\" at line 2 column 20, expected ')' or identifier in parameter list",
+        ],
+    ),
+    (
+        "bad9.bminor",
+        &[
+            "Unexpected ')' at line 2 column 34, expected identifier at start of parameter",
+        ],
+    ),
+    (
+        "bad10.bminor",
+        &[
+            "Unexpected keyword 'else' at line 14 column 5, expected '}' or a statement in block",
+        ],
+    ),
+    (
+        "bad11.bminor",
+        &[
+            "Unexpected identifier 'y' at line 6 column 13, expected ',' or ')' after function argument",
+        ],
+    ),
+    (
+        "bad12.bminor",
+        &[
+            "Unexpected '{' at line 5 column 24, expected '=' or ';' after function type",
+        ],
+    ),
+    (
+        "bad13.bminor",
+        &[
+            "Unexpected keyword 'integer' at line 5 column 5, expected '}' or a statement in block",
+            "Unexpected end of tokens after token at line 5 column 15.",
+        ],
+    ),
+    ("bad14.bminor", &["Unexpected '(' at line 2 column 11, expected a type"]),
+    (
+        "bad15.bminor",
+        &[
+            "Unexpected keyword 'integer' at line 2 column 9, expected '[' after 'array'",
+        ],
+    ),
+    ("bad16.bminor", &["Unexpected '=' at line 3 column 17, expected an expression"]),
+    ("bad17.bminor", &["Unexpected '+' at line 5 column 9, expected an expression"]),
+    (
+        "bad18.bminor",
+        &[
+            "Integer literal 99999999999999999999 at line 2 column 13 does not fit in 64 bits",
+        ],
+    ),
+    (
+        "bad19.bminor",
+        &[
+            "Unexpected identifier 'i' at line 5 column 11, expected '(' after 'while'",
+        ],
+    ),
+    ("bad20.bminor", &["Unexpected ')' at line 5 column 11, expected an expression"]),
+    (
+        "bad21.bminor",
+        &[
+            "Unexpected identifier 'y' at line 3 column 1, expected ';' after variable declaration",
+            "Unexpected ';' at line 7 column 13, expected an expression",
+            "Unexpected ')' at line 9 column 13, expected an expression",
+            "Unexpected ';' at line 15 column 16, expected an expression",
+        ],
+    ),
+];
+
+#[test]
+fn test_parsing_bad_recovering() {
+    let directories = fs::read_dir("./tests/parser_programs/bad").unwrap();
+    assert_eq!(directories.count(), BAD_PROGRAM_ERRORS.len());
+    for (file, expected) in BAD_PROGRAM_ERRORS.iter() {
+        println!("FILENAME - {}", file);
+        let content = fs::read_to_string(format!("./tests/parser_programs/bad/{}", file)).unwrap();
+        let tokens = scanner::tokenize(content.as_str()).unwrap();
+        let (declaration, errors) = parser::parse_recovering(&tokens);
+        if let Some(declaration) = &declaration {
+            assert_valid_declaration(declaration);
+        }
+        let messages: Vec<String> = errors.iter().map(|error| error.to_string()).collect();
+        assert_eq!(&messages, expected);
+        let source_map = SourceMap::new(content.as_str());
+        for error in errors.iter() {
+            let (line, column) = error.location();
+            assert!(error.to_string().contains(&format!("line {} column {}", line, column)));
+            assert_eq!(source_map.location(error.span().start), (line, column));
+        }
+    }
+}
+
+#[test]
+fn test_parsing_bad_recovering_keeps_declarations() {
+    let content = fs::read_to_string("./tests/parser_programs/bad/bad21.bminor").unwrap();
+    let tokens = scanner::tokenize(content.as_str()).unwrap();
+    let (declaration, _) = parser::parse_recovering(&tokens);
+    let mut names = Vec::new();
+    let mut next = declaration.as_ref();
+    while let Some(declaration) = next {
+        names.push(declaration.name.as_str());
+        next = declaration.next.as_deref();
+    }
+    // the declaration missing its semicolon is replaced, the next one is kept
+    assert_eq!(names, ["", "y", "main", "other"]);
+}
+
+#[test]
+fn test_parsing_good_recovering() {
+    let directories = fs::read_dir("./tests/parser_programs/good").unwrap();
+    for directory in directories {
+        let content: String = fs::read_to_string(directory.unwrap().path()).unwrap();
+        let tokens = scanner::tokenize(content.as_str()).unwrap();
+        let (declaration, errors) = parser::parse_recovering(&tokens);
+        assert!(errors.is_empty());
+        // empty programs have no declarations instead of a placeholder one
+        if let Some(declaration) = declaration {
+            assert_eq!(Some(declaration), parser::parse(&tokens).ok());
+        }
+    }
+}
+
+#[test]
+fn test_parsing_lazily() {
+    let directories = fs::read_dir("./tests/parser_programs/good").unwrap();
+    for directory in directories {
+        let canonicalized = directory.unwrap().path();
+        let content: String = fs::read_to_string(canonicalized).unwrap();
+        let tokens = scanner::tokenize(content.as_str()).unwrap();
+        let collected = parser::parse(&tokens).unwrap();
+        let lazy = parser::parse_tokens(scanner::Lexer::new(content.as_str())).unwrap();
+        assert_eq!(collected, lazy);
+    }
+}
+
+#[test]
+fn test_parsing_precedence() {
+    let golden = fs::read_to_string("./tests/parser_programs/expressions.golden").unwrap();
+    for line in golden.lines() {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (expression, expected) = line.split_at(line.find(" => ").unwrap());
+        let source = format!("x: integer = {};", expression);
+        let tokens = scanner::tokenize(&source).unwrap();
+        let declaration = parser::parse(&tokens).unwrap();
+        let parenthesised = declaration.value.unwrap().to_string();
+        assert_eq!(parenthesised, &expected[4..], "{}", expression);
+    }
+}
+
+/// Xorshift pseudo-random number generator, good enough to shuffle tokens
+/// around deterministically.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+fn good_programs() -> Vec<String> {
+    let mut programs = Vec::new();
+    for directory in fs::read_dir("./tests/parser_programs/good").unwrap() {
+        programs.push(fs::read_to_string(directory.unwrap().path()).unwrap());
+    }
+    programs
+}
+
+#[test]
+fn test_parsing_prefixes_never_panics() {
+    for program in good_programs() {
+        let tokens = scanner::tokenize(&program).unwrap();
+        for length in 0..tokens.len() {
+            let _ = parser::parse(&tokens[..length]);
+            let _ = parser::parse_recovering(&tokens[..length]);
+        }
+    }
+}
+
+#[test]
+fn test_parsing_random_tokens_never_panics() {
+    let sample = "array boolean char else float for function if integer print return string \
+                  void while x 1 1.5 true 'a' \"s\" = == != + ++ - -- / * { } ( ) [ ] \
+                  < <= > >= & && | || % : ; , ^ !";
+    let extensions = scanner::Extensions { floats: true, ..scanner::Extensions::default() };
+    let (trivia_tokens, _) = scanner::tokenize_with_trivia(sample, extensions);
+    let pool: Vec<scanner::Token> = trivia_tokens.into_iter().map(|token| token.token).collect();
+    let programs = good_programs();
+    let programs: Vec<Vec<scanner::Token>> =
+        programs.iter().map(|program| scanner::tokenize(program).unwrap()).collect();
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for _ in 0..20000 {
+        let length = random.next(40);
+        let tokens: Vec<scanner::Token> =
+            (0..length).map(|_| pool[random.next(pool.len())].clone()).collect();
+        let _ = parser::parse(&tokens);
+        let _ = parser::parse_recovering(&tokens);
+    }
+    for _ in 0..20000 {
+        let mut tokens = programs[random.next(programs.len())].clone();
+        for _ in 0..=random.next(3) {
+            if tokens.is_empty() {
+                break;
+            }
+            let index = random.next(tokens.len());
+            match random.next(3) {
+                0 => {
+                    tokens.remove(index);
+                }
+                1 => tokens.insert(index, pool[random.next(pool.len())].clone()),
+                _ => tokens[index] = pool[random.next(pool.len())].clone(),
+            }
         }
-        assert!(b);
+        let _ = parser::parse(&tokens);
+        let _ = parser::parse_recovering(&tokens);
     }
 }
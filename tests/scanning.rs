@@ -1,5 +1,7 @@
 use rbm::scanner;
+use rbm::source::SourceMap;
 use std::fs;
+use std::path::{Path, PathBuf};
 
 #[test]
 fn test_scanning_good() {
@@ -24,3 +26,81 @@ fn test_scanning_bad() {
         assert!(tokens.is_err());
     }
 }
+
+#[test]
+fn test_scanning_bad_recovering() {
+    let directories = fs::read_dir("./tests/scanner_programs/bad").unwrap();
+    for directory in directories {
+        let canonicalized = directory.unwrap().path();
+        println!("FILENAME - {:?}", canonicalized);
+        let content: String = fs::read_to_string(canonicalized).unwrap();
+        let (_, errors) = scanner::tokenize_recovering(content.as_str(), Default::default());
+        assert!(!errors.is_empty());
+        let source_map = SourceMap::new(content.as_str());
+        for error in errors.iter() {
+            assert_eq!(source_map.location(error.span().start), error.location());
+        }
+    }
+}
+
+#[test]
+fn test_scanning_all_errors() {
+    let content = fs::read_to_string("./tests/scanner_programs/bad/bad1.bminor").unwrap();
+    let (tokens, errors) = scanner::tokenize_recovering(content.as_str(), Default::default());
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(errors.len(), 3);
+}
+
+#[test]
+fn test_scanning_spans() {
+    let directories = fs::read_dir("./tests/scanner_programs/correct").unwrap();
+    for directory in directories {
+        let canonicalized = directory.unwrap().path();
+        println!("FILENAME - {:?}", canonicalized);
+        let content: String = fs::read_to_string(canonicalized).unwrap();
+        let source_map = SourceMap::new(content.as_str());
+        for token in scanner::tokenize(content.as_str()).unwrap() {
+            assert_eq!(&content[token.span.start..token.span.end], token.lexeme);
+            assert_eq!(source_map.location(token.span.start), (token.line, token.column));
+        }
+    }
+}
+
+#[test]
+fn test_scanning_line_endings() {
+    let content = fs::read_to_string("./tests/scanner_programs/correct/good19.bminor").unwrap();
+    let tokens = scanner::tokenize(content.as_str()).unwrap();
+    let locations: Vec<(usize, usize)> = tokens
+        .iter()
+        .filter(|token| token.lexeme == ";")
+        .map(|token| (token.line, token.column))
+        .collect();
+    assert_eq!(locations, [(2, 15), (3, 17), (6, 14)]);
+    let content = fs::read_to_string("./tests/scanner_programs/correct/good21.bminor").unwrap();
+    let tokens = scanner::tokenize(content.as_str()).unwrap();
+    assert_eq!((tokens[0].line, tokens[0].column), (2, 1));
+    assert_eq!(tokens[0].span.start, content.find('x').unwrap());
+}
+
+fn collect_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_files(&path, files);
+        } else {
+            files.push(path);
+        }
+    }
+}
+
+#[test]
+fn test_scanning_trivia_round_trip() {
+    let mut files = Vec::new();
+    collect_files(Path::new("./tests"), &mut files);
+    for file in files {
+        println!("FILENAME - {:?}", file);
+        let content: String = fs::read_to_string(file).unwrap();
+        let (tokens, _) = scanner::tokenize_with_trivia(&content, scanner::Extensions::default());
+        assert_eq!(scanner::to_source(&tokens), content);
+    }
+}